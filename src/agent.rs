@@ -0,0 +1,154 @@
+use bevy::prelude::Resource;
+
+use crate::{
+    ai::{AStar, Greedy, HamiltonianCycle, Pathfinder},
+    components::{Direction, Position},
+    sim::{BoardMode, Snake, SnakeSim},
+};
+
+// A bot that steers a snake, it gets a view of the board before every tick
+pub trait Agent: Send + Sync {
+    fn next_direction(&mut self, board: &BoardView) -> Direction;
+
+    // Tiles the last choice plans to move through, for the path overlay
+    fn path(&self) -> &[Position] {
+        &[]
+    }
+}
+
+// Read-only view of the board from the point of view of one snake
+#[derive(Clone, Copy)]
+pub struct BoardView<'a> {
+    sim: &'a SnakeSim,
+    player: usize,
+}
+
+impl<'a> BoardView<'a> {
+    pub fn new(sim: &'a SnakeSim, player: usize) -> Self {
+        Self { sim, player }
+    }
+
+    // Index of the snake the agent steers
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn head(&self) -> Position {
+        self.me().head()
+    }
+
+    // Head first
+    pub fn body(&self) -> impl Iterator<Item = Position> + 'a {
+        self.me().body()
+    }
+
+    pub fn length(&self) -> usize {
+        self.me().length()
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.me().direction()
+    }
+
+    pub fn score(&self) -> u32 {
+        self.me().score()
+    }
+
+    // Every snake including this one, dead snakes stay on the board
+    pub fn snakes(&self) -> &'a [Snake] {
+        self.sim.snakes()
+    }
+
+    pub fn food(&self) -> impl Iterator<Item = Position> + 'a {
+        self.sim.food().iter().map(|food| food.pos)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.sim.width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.sim.height()
+    }
+
+    // Whether snakes leaving the board come back on the other side instead of hitting the border
+    pub fn wraps(&self) -> bool {
+        self.sim.rules().board == BoardMode::Wrap
+    }
+
+    pub fn in_bounds(&self, pos: Position) -> bool {
+        self.sim.in_bounds(pos)
+    }
+
+    // Tile a move leads onto, None when it runs into the border
+    pub fn neighbour(&self, pos: Position, direction: Direction) -> Option<Position> {
+        self.sim.neighbour(pos, direction)
+    }
+
+    // Whether a wall of the level lies on a tile
+    pub fn is_wall(&self, pos: Position) -> bool {
+        self.sim.is_wall(pos)
+    }
+
+    // Whether any snake body lies on a tile
+    pub fn is_occupied(&self, pos: Position) -> bool {
+        self.snakes().iter().any(|snake| snake.body().any(|body| body == pos))
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.sim.ticks()
+    }
+
+    fn me(&self) -> &'a Snake {
+        self.sim.snake(self.player)
+    }
+}
+
+type AgentFactory = Box<dyn Fn() -> Box<dyn Agent> + Send + Sync>;
+
+// Agents that can take over the snake of the player, by name
+#[derive(Resource)]
+pub struct AgentRegistry {
+    agents: Vec<(String, AgentFactory)>,
+}
+
+impl Default for AgentRegistry {
+    fn default() -> Self {
+        let mut registry = Self { agents: Vec::new() };
+        registry.register("astar", || Box::new(AStar::default()));
+        registry.register("hamiltonian", || Box::new(HamiltonianCycle::default()));
+        registry.register("greedy", || Box::new(Greedy));
+        registry.register("pathfinder", || Box::new(Pathfinder { lookahead: false }));
+        registry.register("cautious", || Box::new(Pathfinder { lookahead: true }));
+        registry
+    }
+}
+
+impl AgentRegistry {
+    // Add an agent, replacing one with the same name
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn() -> Box<dyn Agent> + Send + Sync + 'static,
+    ) {
+        let name = name.into();
+        self.agents.retain(|(existing, _)| *existing != name);
+        self.agents.push((name, Box::new(factory)));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.agents.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().any(|existing| existing == name)
+    }
+
+    // A fresh agent, every snake gets its own
+    pub fn create(&self, name: &str) -> Option<Box<dyn Agent>> {
+        self.agents
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, factory)| factory())
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use crate::{
+    agent::{Agent, BoardView},
+    components::{Direction, Position},
+};
+
+// How well the rival snakes play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AiSkill {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl AiSkill {
+    pub const ALL: [AiSkill; 3] = [Self::Easy, Self::Normal, Self::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+
+    // The next better skill, wrapping around to the easiest
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&skill| skill == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn agent(self) -> Box<dyn Agent> {
+        match self {
+            Self::Easy => Box::new(Greedy),
+            Self::Normal => Box::new(Pathfinder { lookahead: false }),
+            Self::Hard => Box::new(Pathfinder { lookahead: true }),
+        }
+    }
+}
+
+impl std::str::FromStr for AiSkill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|skill| skill.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown AI skill `{s}`"))
+    }
+}
+
+// Heads for the closest food as the crow flies, only avoiding moves that die right away
+pub struct Greedy;
+
+impl Agent for Greedy {
+    fn next_direction(&mut self, board: &BoardView) -> Direction {
+        let blocked = blocked_tiles(board);
+
+        best_move(board, &blocked, |next| {
+            Reverse(board.food().map(|food| manhattan(next, food)).min())
+        })
+    }
+}
+
+// Follows the shortest path to food, with lookahead it stays out of pockets
+// that are too small for its body and away from the heads of other snakes
+pub struct Pathfinder {
+    pub lookahead: bool,
+}
+
+impl Agent for Pathfinder {
+    fn next_direction(&mut self, board: &BoardView) -> Direction {
+        let blocked = blocked_tiles(board);
+        let distances = food_distances(board, &blocked);
+        let length = board.length();
+        let contested = contested_tiles(board);
+
+        best_move(board, &blocked, |next| {
+            // Unreachable food counts as infinitely far away
+            let distance = Reverse(distances.get(&next).copied().unwrap_or(u32::MAX));
+            if !self.lookahead {
+                return (0, true, distance);
+            }
+
+            // Enough room to fit the whole body comes first, then safety from head-on hits
+            let space = flood_fill(board, next, &blocked).min(length);
+            (space, !contested.contains(&next), distance)
+        })
+    }
+}
+
+// Takes the shortest path to food when the snake still fits into the space behind
+// its first step, otherwise it stalls in the largest open area
+#[derive(Default)]
+pub struct AStar {
+    path: Vec<Position>,
+}
+
+impl Agent for AStar {
+    fn next_direction(&mut self, board: &BoardView) -> Direction {
+        let blocked = blocked_tiles(board);
+
+        self.path = shortest_path_to_food(board, board.head(), &blocked).unwrap_or_default();
+        if let Some(&next) = self.path.first()
+            && flood_fill(board, next, &blocked) >= board.length()
+            && let Some(direction) = direction_to(board, board.head(), next)
+        {
+            return direction;
+        }
+
+        let direction = best_move(board, &blocked, |next| flood_fill(board, next, &blocked));
+        self.path = board.neighbour(board.head(), direction).into_iter().collect();
+        direction
+    }
+
+    fn path(&self) -> &[Position] {
+        &self.path
+    }
+}
+
+// Follows a fixed tour through every tile, so it fills the board without ever
+// trapping itself. Uses A* until the head is on the tour in the right direction.
+// On boards with two odd sides the tour leaves a corner out: food there is taken
+// with a detour when passing by, or by A* if no detour opened for a whole lap.
+#[derive(Default)]
+pub struct HamiltonianCycle {
+    size: (i32, i32),
+    cycle: Vec<Position>,
+    order: HashMap<Position, usize>,
+    corner: Option<Position>, // left out of the tour on boards with two odd sides
+    resume: Option<usize>, // tour index to go on with after a detour over the corner
+    waiting: usize, // ticks the food on the corner has been waiting for a detour
+    path: Vec<Position>,
+    fallback: AStar,
+}
+
+impl Agent for HamiltonianCycle {
+    fn next_direction(&mut self, board: &BoardView) -> Direction {
+        // The tour only depends on the board size
+        if self.size != (board.width(), board.height()) {
+            self.size = (board.width(), board.height());
+            self.cycle = hamiltonian_cycle(board.width(), board.height());
+            self.order = self.cycle.iter().enumerate().map(|(index, &pos)| (pos, index)).collect();
+            self.corner = (0..board.width())
+                .flat_map(|x| (0..board.height()).map(move |y| Position { x, y }))
+                .find(|pos| !self.order.contains_key(pos))
+                .filter(|_| !self.cycle.is_empty());
+        }
+
+        let head = board.head();
+        let food: HashSet<Position> = board.food().collect();
+        let is_free = |pos: Position| !board.is_occupied(pos);
+
+        // Fetch the food on the corner with A* once the tour missed it for a lap
+        let corner_food = self.corner.is_some_and(|corner| food.contains(&corner));
+        self.waiting = if corner_food { self.waiting + 1 } else { 0 };
+        if self.waiting > self.cycle.len() {
+            let direction = self.fallback.next_direction(board);
+            self.path = self.fallback.path.clone();
+            return direction;
+        }
+
+        // Tour tile to move onto next
+        let next_index = match self.order.get(&head) {
+            Some(&index) => Some((index + 1) % self.cycle.len()),
+            None if Some(head) == self.corner => self.resume.take(),
+            None => None,
+        };
+
+        if let Some(next_index) = next_index {
+            let next = self.cycle[next_index];
+            let after = self.cycle[(next_index + 1) % self.cycle.len()];
+
+            // Swap the next tile for the corner when food lies there
+            if let Some(corner) = self.corner
+                && food.contains(&corner)
+                && is_free(corner)
+                && direction_to(board, corner, after).is_some()
+                && let Some(direction) = direction_to(board, head, corner)
+            {
+                self.resume = Some((next_index + 1) % self.cycle.len());
+                self.waiting = 0;
+                self.path = vec![corner];
+                return direction;
+            }
+
+            if is_free(next) && let Some(direction) = direction_to(board, head, next) {
+                // Show the tour up to the next food
+                self.path.clear();
+                for offset in 0..self.cycle.len() {
+                    let pos = self.cycle[(next_index + offset) % self.cycle.len()];
+                    self.path.push(pos);
+                    if food.contains(&pos) {
+                        break;
+                    }
+                }
+                return direction;
+            }
+        }
+
+        let direction = self.fallback.next_direction(board);
+        self.path = self.fallback.path.clone();
+        direction
+    }
+
+    fn path(&self) -> &[Position] {
+        &self.path
+    }
+}
+
+// Closed tour through the tiles of a board, each tile next to the one before.
+// Boards with two odd sides have no such tour, the top left corner is left out there.
+pub fn hamiltonian_cycle(width: i32, height: i32) -> Vec<Position> {
+    // Zigzag along the even side
+    if height % 2 == 1 && width % 2 == 0 {
+        return hamiltonian_cycle(height, width)
+            .into_iter()
+            .map(|pos| Position { x: pos.y, y: pos.x })
+            .collect();
+    }
+    if width < 2 || height < 2 {
+        return Vec::new();
+    }
+
+    // Rows back and forth, leaving the first column free for the way back
+    let mut cycle = vec![Position { x: 0, y: 0 }];
+    let rows = if height % 2 == 0 { height } else { height - 2 };
+    for y in 0..rows {
+        if y % 2 == 0 {
+            cycle.extend((1..width).map(|x| Position { x, y }));
+        } else {
+            cycle.extend((1..width).rev().map(|x| Position { x, y }));
+        }
+    }
+
+    // With an odd height the last two rows go column by column and skip the top left corner
+    let mut top = height - 1;
+    if height % 2 == 1 {
+        for (column, x) in (1..width).rev().enumerate() {
+            let ys = if column % 2 == 0 { [height - 2, height - 1] } else { [height - 1, height - 2] };
+            cycle.extend(ys.map(|y| Position { x, y }));
+        }
+        top = height - 2;
+    }
+
+    cycle.extend((1..=top).rev().map(|y| Position { x: 0, y }));
+    cycle
+}
+
+// Tiles that kill a snake moving onto them
+fn blocked_tiles(board: &BoardView) -> HashSet<Position> {
+    board.snakes().iter().flat_map(|snake| snake.body()).collect()
+}
+
+// Tiles the other living snakes can move onto next tick
+fn contested_tiles(board: &BoardView) -> HashSet<Position> {
+    board.snakes()
+        .iter()
+        .enumerate()
+        .filter(|&(other, snake)| other != board.player() && snake.is_alive())
+        .flat_map(|(_, snake)| {
+            Direction::ALL
+                .into_iter()
+                .filter_map(move |direction| board.neighbour(snake.head(), direction))
+        })
+        .collect()
+}
+
+// Highest scoring move that doesn't die right away, straight ahead wins ties
+fn best_move<K: Ord>(
+    board: &BoardView,
+    blocked: &HashSet<Position>,
+    mut score: impl FnMut(Position) -> K,
+) -> Direction {
+    let current = board.direction();
+
+    let mut best: Option<(K, Direction)> = None;
+    for direction in [current, current.turn_left(), current.turn_right()] {
+        let Some(next) = board.neighbour(board.head(), direction) else {
+            continue;
+        };
+        if blocked.contains(&next) {
+            continue;
+        }
+
+        let value = score(next);
+        if best.as_ref().is_none_or(|(best_value, _)| value > *best_value) {
+            best = Some((value, direction));
+        }
+    }
+
+    // Every move is deadly, keep going
+    best.map_or(current, |(_, direction)| direction)
+}
+
+// Steps from every free tile to the closest food
+fn food_distances(board: &BoardView, blocked: &HashSet<Position>) -> HashMap<Position, u32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for food in board.food() {
+        distances.insert(food, 0);
+        queue.push_back(food);
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        let distance = distances[&pos];
+        for direction in Direction::ALL {
+            let Some(next) = board.neighbour(pos, direction) else {
+                continue;
+            };
+            if blocked.contains(&next) || distances.contains_key(&next) {
+                continue;
+            }
+            distances.insert(next, distance + 1);
+            queue.push_back(next);
+        }
+    }
+
+    distances
+}
+
+// Path from a position to the closest reachable food, without the start
+fn shortest_path_to_food(board: &BoardView, start: Position, blocked: &HashSet<Position>) -> Option<Vec<Position>> {
+    let food: Vec<Position> = board.food().collect();
+    let estimate = |pos: Position| food.iter().map(|&target| distance(board, pos, target)).min();
+
+    let mut open = BinaryHeap::from([(Reverse(estimate(start)?), Reverse(0), start.x, start.y)]);
+    let mut came_from: HashMap<Position, Position> = HashMap::new();
+    let mut costs = HashMap::from([(start, 0)]);
+
+    while let Some((_, Reverse(cost), x, y)) = open.pop() {
+        let pos = Position { x, y };
+        if cost > costs[&pos] {
+            continue;
+        }
+
+        if food.contains(&pos) {
+            let mut path = vec![pos];
+            while let Some(&previous) = came_from.get(path.last()?) {
+                path.push(previous);
+            }
+            path.pop();
+            path.reverse();
+            return Some(path);
+        }
+
+        for direction in Direction::ALL {
+            let Some(next) = board.neighbour(pos, direction) else {
+                continue;
+            };
+            if blocked.contains(&next) || costs.get(&next).is_some_and(|&known| known <= cost + 1) {
+                continue;
+            }
+            costs.insert(next, cost + 1);
+            came_from.insert(next, pos);
+            open.push((Reverse(cost + 1 + estimate(next)?), Reverse(cost + 1), next.x, next.y));
+        }
+    }
+
+    None
+}
+
+// Direction that moves from a tile onto a neighbouring one
+fn direction_to(board: &BoardView, from: Position, to: Position) -> Option<Direction> {
+    Direction::ALL.into_iter().find(|&direction| board.neighbour(from, direction) == Some(to))
+}
+
+// Number of free tiles reachable from a position
+fn flood_fill(board: &BoardView, start: Position, blocked: &HashSet<Position>) -> usize {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(pos) = queue.pop_front() {
+        for direction in Direction::ALL {
+            if let Some(next) = board.neighbour(pos, direction)
+                && !blocked.contains(&next)
+                && seen.insert(next)
+            {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    seen.len()
+}
+
+fn manhattan(a: Position, b: Position) -> u32 {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y)
+}
+
+// Fewest moves between two tiles on an empty board, taking the shortcut over the edge on wrap boards
+fn distance(board: &BoardView, a: Position, b: Position) -> u32 {
+    let (dx, dy) = (a.x.abs_diff(b.x), a.y.abs_diff(b.y));
+    if board.wraps() {
+        dx.min(board.width() as u32 - dx) + dy.min(board.height() as u32 - dy)
+    } else {
+        dx + dy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{GridConfig, Layout, Rules, SnakeSim};
+
+    fn adjacent(a: Position, b: Position) -> bool {
+        manhattan(a, b) == 1
+    }
+
+    // Every tile of the tour is next to the one before, including the way back to the start
+    fn assert_closed(cycle: &[Position]) {
+        for (index, &pos) in cycle.iter().enumerate() {
+            let next = cycle[(index + 1) % cycle.len()];
+            assert!(adjacent(pos, next), "{pos:?} -> {next:?}");
+        }
+    }
+
+    #[test]
+    fn cycle_visits_every_tile_once_on_boards_with_an_even_side() {
+        for (width, height) in [(2, 2), (4, 4), (6, 4), (4, 7), (5, 6), (10, 10)] {
+            let cycle = hamiltonian_cycle(width, height);
+            let tiles: HashSet<Position> = cycle.iter().copied().collect();
+
+            assert_eq!(cycle.len(), (width * height) as usize, "{width}x{height}");
+            assert_eq!(tiles.len(), cycle.len(), "{width}x{height} visits a tile twice");
+            assert!(tiles.iter().all(|&pos| pos.x >= 0 && pos.x < width && pos.y >= 0 && pos.y < height));
+            assert_closed(&cycle);
+        }
+    }
+
+    #[test]
+    fn cycle_leaves_out_the_top_left_corner_on_odd_boards() {
+        for (width, height) in [(3, 3), (5, 7), (15, 15)] {
+            let cycle = hamiltonian_cycle(width, height);
+            let tiles: HashSet<Position> = cycle.iter().copied().collect();
+
+            assert_eq!(cycle.len(), (width * height - 1) as usize, "{width}x{height}");
+            assert_eq!(tiles.len(), cycle.len());
+            assert!(!tiles.contains(&Position { x: 0, y: height - 1 }));
+            assert_closed(&cycle);
+        }
+    }
+
+    #[test]
+    fn food_on_the_left_out_corner_gets_eaten() {
+        let grid = GridConfig { width: 5, height: 5, start: Position { x: 2, y: 1 }, ..GridConfig::default() };
+        let layout = Layout {
+            food_zone: HashSet::from([Position { x: 0, y: 4 }]),
+            ..Layout::default()
+        };
+        let mut sim = SnakeSim::new(grid, Rules::default(), 3).with_layout(layout);
+        let mut agent = HamiltonianCycle::default();
+
+        for _ in 0..500 {
+            let direction = agent.next_direction(&BoardView::new(&sim, 0));
+            sim.tick(&[direction]);
+            assert!(!sim.is_over(), "died after {} ticks", sim.ticks());
+        }
+        assert!(sim.snake(0).score() >= 3, "score {}", sim.snake(0).score());
+    }
+}
@@ -0,0 +1,69 @@
+use snakegame::{
+    cli::{parse_grid, parse_value},
+    net::{NetServer, ServerConfig, DEFAULT_PORT},
+    sim::{BoardMode, Difficulty, GridConfig, MAX_PLAYERS},
+};
+
+const USAGE: &str = "usage: server [--port N] [--bind ADDR] [--players N] [--grid WxH] [--length N] [--wrap] \
+[--difficulty NAME] [--seed N]";
+
+fn main() {
+    let (bind, config) = parse_args(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("server: {err}\n{USAGE}");
+        std::process::exit(2);
+    });
+
+    let mut server = NetServer::bind(bind.as_str(), config).unwrap_or_else(|err| {
+        eprintln!("server: could not listen on {bind}: {err}");
+        std::process::exit(2);
+    });
+    match server.local_addr() {
+        Ok(addr) => println!("Listening on {addr}"),
+        Err(_) => println!("Listening on {bind}"),
+    }
+
+    if let Err(err) = server.run() {
+        eprintln!("server: {err}");
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<(String, ServerConfig), String> {
+    let mut port = DEFAULT_PORT;
+    let mut bind = None;
+    let mut grid = GridConfig::default();
+    let mut difficulty = Difficulty::default();
+    let mut wrap = false;
+    let mut min_players = 2;
+    let mut seed = rand::random();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = parse_value(&arg, args.next())?,
+            "--bind" => bind = Some(parse_value::<String>(&arg, args.next())?),
+            "--players" => min_players = parse_value(&arg, args.next())?,
+            "--grid" => (grid.width, grid.height) = parse_grid(&arg, args.next())?,
+            "--length" => grid.start_length = parse_value(&arg, args.next())?,
+            "--wrap" => wrap = true,
+            "--difficulty" => difficulty = parse_value(&arg, args.next())?,
+            "--seed" => seed = parse_value(&arg, args.next())?,
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    if !(1..=MAX_PLAYERS).contains(&min_players) {
+        return Err(format!("--players must be between 1 and {MAX_PLAYERS}"));
+    }
+    grid.validate(MAX_PLAYERS)?;
+
+    let mut rules = difficulty.rules();
+    if wrap {
+        rules.board = BoardMode::Wrap;
+    }
+    let bind = bind.unwrap_or_else(|| format!("0.0.0.0:{port}"));
+
+    let config = ServerConfig { grid, rules, min_players, seed };
+    config.validate()?;
+    Ok((bind, config))
+}
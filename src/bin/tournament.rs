@@ -0,0 +1,114 @@
+use std::{fs, path::PathBuf};
+
+use snakegame::{
+    agent::AgentRegistry,
+    cli::{parse_grid, parse_value},
+    sim::{BoardMode, Difficulty, GridConfig},
+    tournament::{run_tournament, summarize, to_csv, to_json, AgentSummary, TournamentConfig},
+};
+
+const USAGE: &str = "usage: tournament [--agents a,b,...] [--seeds N] [--first-seed S] [--seed-list 1,2,...] \
+[--grid WxH] [--length N] [--wrap] [--difficulty NAME] [--max-steps N] [--threads N] [--json PATH] [--csv PATH]";
+
+// Where to write the results besides the table
+struct Output {
+    json: Option<PathBuf>,
+    csv: Option<PathBuf>,
+}
+
+fn main() {
+    let registry = AgentRegistry::default();
+    let (config, output) = parse_args(std::env::args().skip(1), &registry).unwrap_or_else(|err| {
+        eprintln!("tournament: {err}\n{USAGE}");
+        std::process::exit(2);
+    });
+
+    let results = run_tournament(&config, &registry).unwrap_or_else(|err| {
+        eprintln!("tournament: {err}");
+        std::process::exit(2);
+    });
+    let summaries = summarize(&results);
+
+    print_table(&summaries);
+
+    let files = [(output.json, to_json(&summaries, &results)), (output.csv, to_csv(&results))];
+    for (path, contents) in files {
+        if let Some(path) = path
+            && let Err(err) = fs::write(&path, contents)
+        {
+            eprintln!("tournament: could not write {}: {err}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    registry: &AgentRegistry,
+) -> Result<(TournamentConfig, Output), String> {
+    let mut agents: Vec<String> = registry.names().map(str::to_string).collect();
+    let (mut count, mut first_seed, mut seed_list) = (20, 0, None);
+    let mut grid = GridConfig::default();
+    let mut difficulty = Difficulty::default();
+    let mut wrap = false;
+    let mut max_steps = 20_000;
+    let mut threads = None;
+    let mut output = Output { json: None, csv: None };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--agents" => agents = parse_list(&arg, args.next())?,
+            "--seeds" => count = parse_value(&arg, args.next())?,
+            "--first-seed" => first_seed = parse_value(&arg, args.next())?,
+            "--seed-list" => seed_list = Some(parse_list(&arg, args.next())?),
+            "--grid" => (grid.width, grid.height) = parse_grid(&arg, args.next())?,
+            "--length" => grid.start_length = parse_value(&arg, args.next())?,
+            "--wrap" => wrap = true,
+            "--difficulty" => difficulty = parse_value(&arg, args.next())?,
+            "--max-steps" => max_steps = parse_value(&arg, args.next())?,
+            "--threads" => threads = Some(parse_value(&arg, args.next())?),
+            "--json" => output.json = Some(parse_value(&arg, args.next())?),
+            "--csv" => output.csv = Some(parse_value(&arg, args.next())?),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    let mut rules = difficulty.rules();
+    if wrap {
+        rules.board = BoardMode::Wrap;
+    }
+    let seeds = seed_list.unwrap_or_else(|| (first_seed..first_seed + count).collect());
+
+    Ok((TournamentConfig { agents, seeds, grid, rules, max_steps, threads }, output))
+}
+
+// Parse a comma separated value like `astar,greedy`
+fn parse_list<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<Vec<T>, String> {
+    let value: String = parse_value(flag, value)?;
+    value
+        .split(',')
+        .map(|item| parse_value(flag, Some(item.trim().to_string())))
+        .collect()
+}
+
+fn print_table(summaries: &[AgentSummary]) {
+    println!(
+        "{:>4}  {:<12} {:>5} {:>10} {:>6} {:>11} {:>10}  deaths",
+        "rank", "agent", "games", "mean score", "best", "mean length", "mean steps"
+    );
+    for summary in summaries {
+        let deaths: Vec<_> = summary.deaths.iter().map(|(cause, count)| format!("{cause} {count}")).collect();
+        println!(
+            "{:>4}  {:<12} {:>5} {:>10.1} {:>6} {:>11.1} {:>10.1}  {}",
+            summary.rank,
+            summary.agent,
+            summary.games,
+            summary.mean_score,
+            summary.best_score,
+            summary.mean_length,
+            summary.mean_steps,
+            deaths.join(", ")
+        );
+    }
+}
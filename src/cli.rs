@@ -0,0 +1,108 @@
+use std::{path::PathBuf, str::FromStr};
+
+use crate::{ai::AiSkill, sim::{CurveParams, Difficulty}};
+
+// Command line options
+#[derive(Debug, Default, Clone)]
+pub struct CliArgs {
+    pub seed: Option<u64>,
+    pub replay: Option<PathBuf>,
+    pub name: Option<String>,
+    pub grid: Option<(i32, i32)>,
+    pub length: Option<u32>,
+    pub wrap: bool,
+    pub difficulty: Option<Difficulty>,
+    pub speedup: Option<String>,
+    pub speedup_params: CurveParams,
+    pub players: Option<usize>,
+    pub rivals: Option<usize>,
+    pub ai_skill: Option<AiSkill>,
+    pub agent: Option<String>,
+    pub connect: Option<String>,
+    pub host: Option<u16>,
+    pub join: Option<String>,
+    pub spectate: Option<u16>,
+    pub level: Option<String>,
+}
+
+impl CliArgs {
+    // Parse the arguments of the current process
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => cli.seed = Some(parse_value(&arg, args.next())?),
+                "--replay" => cli.replay = Some(parse_value(&arg, args.next())?),
+                "--name" => cli.name = Some(parse_value(&arg, args.next())?),
+                "--grid" => cli.grid = Some(parse_grid(&arg, args.next())?),
+                "--length" => cli.length = Some(parse_value(&arg, args.next())?),
+                "--wrap" => cli.wrap = true,
+                "--difficulty" => cli.difficulty = Some(parse_value(&arg, args.next())?),
+                "--speedup" => cli.speedup = Some(parse_value(&arg, args.next())?),
+                "--speedup-every" => cli.speedup_params.every = Some(parse_value(&arg, args.next())?),
+                "--speedup-step" => cli.speedup_params.step_millis = Some(parse_value(&arg, args.next())?),
+                "--speedup-rate" => cli.speedup_params.percent = Some(parse_value(&arg, args.next())?),
+                "--speedup-floor" => cli.speedup_params.min_millis = Some(parse_value(&arg, args.next())?),
+                "--players" => cli.players = Some(parse_value(&arg, args.next())?),
+                "--rivals" => cli.rivals = Some(parse_value(&arg, args.next())?),
+                "--ai-skill" => cli.ai_skill = Some(parse_value(&arg, args.next())?),
+                "--agent" => cli.agent = Some(parse_value(&arg, args.next())?),
+                "--connect" => cli.connect = Some(parse_value(&arg, args.next())?),
+                "--host" => cli.host = Some(parse_value(&arg, args.next())?),
+                "--join" => cli.join = Some(parse_value(&arg, args.next())?),
+                "--spectate" => cli.spectate = Some(parse_value(&arg, args.next())?),
+                "--level" => cli.level = Some(parse_value(&arg, args.next())?),
+                _ => return Err(format!("unknown argument `{arg}`")),
+            }
+        }
+
+        Ok(cli)
+    }
+}
+
+// Parse the value that follows a flag
+pub fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for `{flag}`"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{flag}`"))
+}
+
+// Parse a grid size like `30x20`
+pub fn parse_grid(flag: &str, value: Option<String>) -> Result<(i32, i32), String> {
+    let value: String = parse_value(flag, value)?;
+    let invalid = || format!("invalid grid size `{value}` for `{flag}`, expected WIDTHxHEIGHT");
+
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse().map_err(|_| invalid())?;
+    let height = height.parse().map_err(|_| invalid())?;
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<CliArgs, String> {
+        CliArgs::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn speedup_parameters() {
+        let cli = parse("--speedup stepped --speedup-every 4 --speedup-step 25 --speedup-rate 95 --speedup-floor 60").unwrap();
+        assert_eq!(cli.speedup.as_deref(), Some("stepped"));
+        assert_eq!(
+            cli.speedup_params,
+            CurveParams { every: Some(4), step_millis: Some(25), percent: Some(95), min_millis: Some(60) },
+        );
+
+        assert!(parse("--speedup-floor").is_err());
+        assert!(parse("--speedup-rate fast").is_err());
+    }
+}
@@ -1,11 +1,27 @@
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::Agent;
 
 // Grid Position of an entity
-#[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
+impl Position {
+    // Return the neighbouring position in a direction
+    pub fn step(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Left  => Position { x: self.x - 1, y: self.y },
+            Direction::Right => Position { x: self.x + 1, y: self.y },
+            Direction::Up    => Position { x: self.x,     y: self.y + 1 },
+            Direction::Down  => Position { x: self.x,     y: self.y - 1 },
+        }
+    }
+}
 
 // Grid Size
 #[derive(Component)]
@@ -23,7 +39,7 @@ impl Size {
 }
 
 // Movement direction of the snake
-#[derive(Component, PartialEq, Copy, Clone)]
+#[derive(Component, Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub enum Direction {
     Left,
     Right,
@@ -31,6 +47,8 @@ pub enum Direction {
     Down,
 }
 impl Direction {
+    pub const ALL: [Direction; 4] = [Self::Up, Self::Down, Self::Left, Self::Right];
+
     // Return the opposite of a direction
     pub fn opposite(self) -> Self {
         match self {
@@ -40,14 +58,89 @@ impl Direction {
             Self::Down => Self::Up,
         }
     }
+
+    // Rotate a quarter turn counterclockwise
+    pub fn turn_left(self) -> Self {
+        match self {
+            Self::Up => Self::Left,
+            Self::Left => Self::Down,
+            Self::Down => Self::Right,
+            Self::Right => Self::Up,
+        }
+    }
+
+    // Rotate a quarter turn clockwise
+    pub fn turn_right(self) -> Self {
+        self.turn_left().opposite()
+    }
+
+    // Direction along the dominant axis of a vector, y pointing up
+    pub fn dominant(vector: Vec2) -> Self {
+        if vector.x.abs() > vector.y.abs() {
+            if vector.x > 0.0 { Self::Right } else { Self::Left }
+        } else if vector.y > 0.0 {
+            Self::Up
+        } else {
+            Self::Down
+        }
+    }
 }
 
 // Snake head
 #[derive(Component)]
 pub struct SnakeHead {
     pub direction: Direction,
+    pub player: usize, // index of the snake in the simulation
+}
+
+// Turns of a snake waiting to be applied, one per tick
+#[derive(Component, Default)]
+pub struct DirectionQueue(pub VecDeque<Direction>);
+
+impl DirectionQueue {
+    // Enough for quick double turns without steering far ahead
+    pub const CAPACITY: usize = 3;
+
+    // Queue a turn, returns false if it was dropped
+    pub fn push(&mut self, direction: Direction, current: Direction) -> bool {
+        // Check against the turn it follows so queued reversals are impossible too
+        let previous = self.heading(current);
+
+        if self.0.len() >= Self::CAPACITY || direction == previous || direction == previous.opposite() {
+            return false;
+        }
+
+        self.0.push_back(direction);
+        true
+    }
+
+    // Heading of the snake once all queued turns are applied
+    pub fn heading(&self, current: Direction) -> Direction {
+        self.0.back().copied().unwrap_or(current)
+    }
+}
+
+// Snake steered by an agent instead of the keyboard
+#[derive(Component)]
+pub struct AiSnake {
+    pub agent: Box<dyn Agent>,
+    pub decided_tick: Option<u32>, // last tick a direction was picked for
+}
+
+impl AiSnake {
+    pub fn new(agent: Box<dyn Agent>) -> Self {
+        Self { agent, decided_tick: None }
+    }
 }
 
+// Snake of the player that the autopilot steers
+#[derive(Component)]
+pub struct AutopilotSnake;
+
+// Tile on the path the autopilot plans to take
+#[derive(Component)]
+pub struct PathMarker;
+
 // Snake tail
 #[derive(Component)]
 pub struct SnakeSegment;
@@ -58,4 +151,44 @@ pub struct Food;
 
 // Border
 #[derive(Component)]
-pub struct Border;
\ No newline at end of file
+pub struct Border;
+
+// Snake, food, border and overlay entities that make up a running game
+pub type GameEntityFilter = Or<(With<SnakeHead>, With<SnakeSegment>, With<Food>, With<Border>, With<PathMarker>)>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(queue: &DirectionQueue) -> Vec<Direction> {
+        queue.0.iter().copied().collect()
+    }
+
+    #[test]
+    fn queue_holds_three_turns() {
+        let mut queue = DirectionQueue::default();
+        assert!(queue.push(Direction::Left, Direction::Up));
+        assert!(queue.push(Direction::Up, Direction::Up));
+        assert!(queue.push(Direction::Right, Direction::Up));
+        assert!(!queue.push(Direction::Down, Direction::Up));
+        assert_eq!(queued(&queue), [Direction::Left, Direction::Up, Direction::Right]);
+        assert_eq!(queue.heading(Direction::Up), Direction::Right);
+    }
+
+    #[test]
+    fn turns_are_checked_against_the_last_queued_one() {
+        let mut queue = DirectionQueue::default();
+        assert_eq!(queue.heading(Direction::Up), Direction::Up);
+
+        // Straight on and back are no turns for the current heading
+        assert!(!queue.push(Direction::Up, Direction::Up));
+        assert!(!queue.push(Direction::Down, Direction::Up));
+
+        // After a queued turn left, right would reverse it while down is a turn again
+        assert!(queue.push(Direction::Left, Direction::Up));
+        assert!(!queue.push(Direction::Right, Direction::Up));
+        assert!(!queue.push(Direction::Left, Direction::Up));
+        assert!(queue.push(Direction::Down, Direction::Up));
+        assert_eq!(queued(&queue), [Direction::Left, Direction::Down]);
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    agent::{Agent, BoardView},
+    ai::AiSkill,
+    components::{Direction, Position},
+    level::Level,
+    sim::{DeathCause, GridConfig, Layout, Rules, SnakeSim, StepOutcome},
+};
+
+// What the learning agent sees of the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObservationKind {
+    // Channels for the own body, the own head, other snakes, food and walls, each HEIGHT x WIDTH
+    #[default]
+    Grid,
+    // Inverse distances to a wall, a body and food along 8 rays from the head
+    Rays,
+    // Danger ahead, left and right, the heading and where the closest food is
+    Features,
+}
+
+impl ObservationKind {
+    pub const ALL: [ObservationKind; 3] = [Self::Grid, Self::Rays, Self::Features];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Grid => "grid",
+            Self::Rays => "rays",
+            Self::Features => "features",
+        }
+    }
+
+    // Shape of the observations for a board size
+    pub fn shape(self, width: i32, height: i32) -> Vec<usize> {
+        match self {
+            Self::Grid => vec![GRID_CHANNELS, height as usize, width as usize],
+            Self::Rays => vec![RAYS.len() * 3],
+            Self::Features => vec![FEATURES],
+        }
+    }
+}
+
+impl std::str::FromStr for ObservationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown observation `{s}`"))
+    }
+}
+
+const GRID_CHANNELS: usize = 5;
+const FEATURES: usize = 12;
+
+// Ray directions as steps in x and y, clockwise from up
+const RAYS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+// Flat tensor of values in row-major order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+// Reward for each thing that can happen during a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardConfig {
+    pub food: f32,  // per eaten food
+    pub death: f32, // when the snake dies
+    pub step: f32,  // every step, negative to make the snake hurry
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            food: 1.0,
+            death: -1.0,
+            step: -0.01,
+        }
+    }
+}
+
+// Everything that decides how an environment plays
+#[derive(Debug, Clone, Default)]
+pub struct EnvConfig {
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub layout: Layout, // walls, food zones and goal of a level, empty on an open board
+    pub rivals: usize, // computer snakes sharing the board
+    pub rival_skill: AiSkill,
+    pub observation: ObservationKind,
+    pub reward: RewardConfig,
+    pub max_idle_steps: Option<u32>, // episodes end after this many steps without food
+}
+
+impl EnvConfig {
+    // Play on the board of a level
+    pub fn with_level(self, level: &Level) -> Self {
+        Self {
+            grid: level.grid,
+            layout: level.layout.clone(),
+            ..self
+        }
+    }
+}
+
+// Details about the episode after a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    pub score: u32,
+    pub length: usize,
+    pub steps: u32,
+    pub death: Option<DeathCause>,
+    pub truncated: bool, // ended by the step limit instead of the game
+}
+
+// Result of a step: observation, reward, whether the episode ended and details
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+// A single headless game where the learning agent steers the first snake
+pub struct SnakeEnv {
+    config: EnvConfig,
+    sim: SnakeSim,
+    rivals: Vec<Box<dyn Agent>>,
+    idle_steps: u32,
+    truncated: bool,
+}
+
+impl SnakeEnv {
+    pub fn new(config: EnvConfig) -> Result<Self, String> {
+        config.grid.validate(1 + config.rivals)?;
+        if let Some(pos) = (0..1 + config.rivals)
+            .flat_map(|player| config.grid.start_body(player))
+            .find(|pos| config.layout.walls.contains(pos))
+        {
+            return Err(format!("a snake starts in the wall at ({}, {})", pos.x, pos.y));
+        }
+
+        let mut env = Self {
+            sim: SnakeSim::new(config.grid, config.rules, 0),
+            config,
+            rivals: Vec::new(),
+            idle_steps: 0,
+            truncated: false,
+        };
+        env.reset(0);
+        Ok(env)
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn sim(&self) -> &SnakeSim {
+        &self.sim
+    }
+
+    pub fn observation_shape(&self) -> Vec<usize> {
+        self.config.observation.shape(self.sim.width(), self.sim.height())
+    }
+
+    // Whether the episode has ended and needs a reset
+    pub fn is_done(&self) -> bool {
+        self.sim.is_over() || self.truncated
+    }
+
+    // Start a new episode
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let players = 1 + self.config.rivals;
+        self.sim = SnakeSim::with_players(self.config.grid, self.config.rules, seed, players)
+            .with_rivals(self.config.rivals)
+            .with_layout(self.config.layout.clone());
+        self.rivals = (0..self.config.rivals).map(|_| self.config.rival_skill.agent()).collect();
+        self.idle_steps = 0;
+        self.truncated = false;
+        self.observe()
+    }
+
+    // Move the snake by one tile, the rivals pick their own directions
+    pub fn step(&mut self, direction: Direction) -> StepResult {
+        let mut reward = 0.0;
+
+        if !self.is_done() {
+            let mut directions = vec![direction];
+            for (rival, agent) in self.rivals.iter_mut().enumerate() {
+                directions.push(agent.next_direction(&BoardView::new(&self.sim, rival + 1)));
+            }
+
+            let report = self.sim.tick(&directions);
+            reward += self.config.reward.step;
+            self.idle_steps += 1;
+
+            match report.outcomes.first() {
+                Some(StepOutcome::Ate(_)) => {
+                    reward += self.config.reward.food;
+                    self.idle_steps = 0;
+                }
+                Some(StepOutcome::Died(_)) => reward += self.config.reward.death,
+                _ => {}
+            }
+
+            self.truncated = !self.sim.is_over() && self.config.max_idle_steps.is_some_and(|max| self.idle_steps >= max);
+        }
+
+        StepResult {
+            observation: self.observe(),
+            reward,
+            done: self.is_done(),
+            info: self.info(),
+        }
+    }
+
+    pub fn info(&self) -> StepInfo {
+        let snake = self.sim.snake(0);
+        StepInfo {
+            score: snake.score(),
+            length: snake.length(),
+            steps: self.sim.ticks(),
+            death: snake.death(),
+            truncated: self.truncated,
+        }
+    }
+
+    // Observation of the current board
+    pub fn observe(&self) -> Observation {
+        let board = BoardView::new(&self.sim, 0);
+        let kind = self.config.observation;
+        let data = match kind {
+            ObservationKind::Grid => grid_observation(&board),
+            ObservationKind::Rays => ray_observation(&board),
+            ObservationKind::Features => feature_observation(&board),
+        };
+
+        Observation {
+            shape: kind.shape(board.width(), board.height()),
+            data,
+        }
+    }
+}
+
+// Several environments stepped together, spread over long-lived worker threads
+// that each own a share of the environments
+pub struct BatchEnv {
+    workers: Vec<Worker>,
+    len: usize,
+}
+
+// An environment and the seed of its next episode
+struct Slot {
+    env: SnakeEnv,
+    next_seed: u64,
+}
+
+// Environments travel to the worker thread for a step and come back with the results
+type Job = (Vec<Slot>, Vec<Direction>);
+type Done = (Vec<Slot>, Vec<StepResult>);
+
+struct Worker {
+    slots: Vec<Slot>, // empty while the thread steps them
+    jobs: Option<Sender<Job>>,
+    done: Receiver<Done>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn(slots: Vec<Slot>, stride: u64) -> Self {
+        let (jobs, job_reader) = mpsc::channel::<Job>();
+        let (done_writer, done) = mpsc::channel::<Done>();
+
+        let handle = thread::spawn(move || {
+            for (mut slots, directions) in job_reader {
+                let results = slots
+                    .iter_mut()
+                    .zip(directions)
+                    .map(|(slot, direction)| {
+                        let mut step = slot.env.step(direction);
+                        if step.done {
+                            step.observation = slot.env.reset(slot.next_seed);
+                            slot.next_seed = slot.next_seed.wrapping_add(stride);
+                        }
+                        step
+                    })
+                    .collect();
+                if done_writer.send((slots, results)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            slots,
+            jobs: Some(jobs),
+            done,
+            handle: Some(handle),
+        }
+    }
+
+    // Pass on the panic of the thread, its environments are lost with it
+    fn fail(&mut self) -> ! {
+        self.jobs = None;
+        match self.handle.take().map(JoinHandle::join) {
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            _ => panic!("environment worker stopped"),
+        }
+    }
+}
+
+impl BatchEnv {
+    pub fn new(config: EnvConfig, count: usize) -> Result<Self, String> {
+        let mut slots = (0..count)
+            .map(|_| SnakeEnv::new(config.clone()).map(|env| Slot { env, next_seed: 0 }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk = count.div_ceil(threads).max(1);
+
+        let mut workers = Vec::new();
+        while !slots.is_empty() {
+            let rest = slots.split_off(chunk.min(slots.len()));
+            workers.push(Worker::spawn(slots, count as u64));
+            slots = rest;
+        }
+
+        Ok(Self { workers, len: count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn envs(&self) -> impl Iterator<Item = &SnakeEnv> {
+        self.workers.iter().flat_map(|worker| &worker.slots).map(|slot| &slot.env)
+    }
+
+    // Start new episodes, environment i plays seed + i
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        let count = self.len as u64;
+        self.workers
+            .iter_mut()
+            .flat_map(|worker| &mut worker.slots)
+            .zip(0..)
+            .map(|(slot, index)| {
+                let seed = seed.wrapping_add(index);
+                slot.next_seed = seed.wrapping_add(count);
+                slot.env.reset(seed)
+            })
+            .collect()
+    }
+
+    // Step every environment with its own direction. Finished episodes start over right
+    // away, their result has the reward and info of the end but the first observation
+    // of the next episode.
+    pub fn step(&mut self, directions: &[Direction]) -> Vec<StepResult> {
+        assert_eq!(directions.len(), self.len, "one direction per environment");
+
+        // Hand out all the work first so the workers run side by side
+        let mut offset = 0;
+        for worker in &mut self.workers {
+            let slots = std::mem::take(&mut worker.slots);
+            let share = directions[offset..offset + slots.len()].to_vec();
+            offset += slots.len();
+
+            let sent = worker.jobs.as_ref().is_some_and(|jobs| jobs.send((slots, share)).is_ok());
+            if !sent {
+                worker.fail();
+            }
+        }
+
+        let mut results = Vec::with_capacity(self.len);
+        for worker in &mut self.workers {
+            match worker.done.recv() {
+                Ok((slots, done)) => {
+                    worker.slots = slots;
+                    results.extend(done);
+                }
+                Err(_) => worker.fail(),
+            }
+        }
+        results
+    }
+}
+
+impl Drop for BatchEnv {
+    // Close the job queues and wait for the threads to finish
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            worker.jobs = None;
+        }
+        for worker in &mut self.workers {
+            if let Some(handle) = worker.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+fn grid_observation(board: &BoardView) -> Vec<f32> {
+    let (width, height) = (board.width() as usize, board.height() as usize);
+    let mut data = vec![0.0; GRID_CHANNELS * width * height];
+    let mut set = |channel: usize, pos: Position| {
+        if board.in_bounds(pos) {
+            data[(channel * height + pos.y as usize) * width + pos.x as usize] = 1.0;
+        }
+    };
+
+    for (player, snake) in board.snakes().iter().enumerate() {
+        let channel = if player == board.player() { 0 } else { 2 };
+        snake.body().for_each(|pos| set(channel, pos));
+    }
+    set(1, board.head());
+    board.food().for_each(|pos| set(3, pos));
+    for y in 0..board.height() {
+        for x in 0..board.width() {
+            let pos = Position { x, y };
+            if board.is_wall(pos) {
+                set(4, pos);
+            }
+        }
+    }
+
+    data
+}
+
+fn ray_observation(board: &BoardView) -> Vec<f32> {
+    let reach = board.width().max(board.height());
+    let inverse = |distance: Option<i32>| distance.map_or(0.0, |distance| 1.0 / distance as f32);
+    let food: Vec<Position> = board.food().collect();
+
+    let mut data = Vec::with_capacity(RAYS.len() * 3);
+    for (dx, dy) in RAYS {
+        let (mut wall, mut body, mut found_food) = (None, None, None);
+        let mut pos = board.head();
+
+        for distance in 1..=reach {
+            pos = Position { x: pos.x + dx, y: pos.y + dy };
+            if !board.in_bounds(pos) {
+                if !board.wraps() {
+                    wall = Some(distance);
+                    break;
+                }
+                pos = Position {
+                    x: pos.x.rem_euclid(board.width()),
+                    y: pos.y.rem_euclid(board.height()),
+                };
+            }
+            if board.is_wall(pos) {
+                wall = Some(distance);
+                break;
+            }
+            if body.is_none() && board.is_occupied(pos) {
+                body = Some(distance);
+            }
+            if found_food.is_none() && food.contains(&pos) {
+                found_food = Some(distance);
+            }
+        }
+
+        data.extend([inverse(wall), inverse(body), inverse(found_food)]);
+    }
+
+    data
+}
+
+fn feature_observation(board: &BoardView) -> Vec<f32> {
+    let flag = |value: bool| if value { 1.0 } else { 0.0 };
+    let head = board.head();
+    let heading = board.direction();
+    let danger = |direction: Direction| {
+        board.neighbour(head, direction).is_none_or(|next| board.is_occupied(next) || board.is_wall(next))
+    };
+
+    let closest = board
+        .food()
+        .min_by_key(|food| head.x.abs_diff(food.x) + head.y.abs_diff(food.y));
+
+    let mut data = Vec::with_capacity(FEATURES);
+    data.extend([danger(heading), danger(heading.turn_left()), danger(heading.turn_right())].map(flag));
+    data.extend(Direction::ALL.map(|direction| flag(direction == heading)));
+    data.extend(match closest {
+        // Up, down, left and right like the heading
+        Some(food) => [food.y > head.y, food.y < head.y, food.x < head.x, food.x > head.x].map(flag),
+        None => [0.0; 4],
+    });
+    data.push(board.length() as f32 / (board.width() * board.height()) as f32);
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The snake starts at (3, 3) heading up, four steps left run it into the border
+    const INTO_THE_BORDER: [Direction; 4] = [Direction::Left; 4];
+
+    fn config() -> EnvConfig {
+        EnvConfig {
+            rivals: 1,
+            ..EnvConfig::default()
+        }
+    }
+
+    #[test]
+    fn reset_with_a_seed_gives_the_same_episode() {
+        let mut first = SnakeEnv::new(config()).unwrap();
+        let mut second = SnakeEnv::new(config()).unwrap();
+        assert_eq!(first.reset(9), second.reset(9));
+
+        for direction in [Direction::Up, Direction::Right, Direction::Right, Direction::Down] {
+            assert_eq!(first.step(direction), second.step(direction));
+        }
+        assert_eq!(first.reset(9), second.reset(9));
+        assert_eq!(first.sim().state_hash(), second.sim().state_hash());
+    }
+
+    #[test]
+    fn batch_reset_plays_consecutive_seeds() {
+        let mut batch = BatchEnv::new(config(), 5).unwrap();
+        let mut again = BatchEnv::new(config(), 5).unwrap();
+        let observations = batch.reset(20);
+        assert_eq!(observations, again.reset(20));
+
+        let mut single = SnakeEnv::new(config()).unwrap();
+        for (index, observation) in observations.iter().enumerate() {
+            assert_eq!(*observation, single.reset(20 + index as u64));
+        }
+    }
+
+    #[test]
+    fn death_ends_the_episode_with_the_death_reward() {
+        let mut env = SnakeEnv::new(EnvConfig::default()).unwrap();
+        env.reset(1);
+        let reward = RewardConfig::default();
+
+        let (last, steps) = INTO_THE_BORDER.split_last().unwrap();
+        for &direction in steps {
+            let result = env.step(direction);
+            assert!(!result.done);
+            assert_eq!(result.info.death, None);
+        }
+
+        let result = env.step(*last);
+        assert!(result.done);
+        assert!(!result.info.truncated);
+        assert_eq!(result.info.death, Some(DeathCause::Border));
+        assert_eq!(result.reward, reward.step + reward.death);
+
+        // Nothing happens until the next reset
+        let result = env.step(Direction::Up);
+        assert!(result.done);
+        assert_eq!(result.reward, 0.0);
+    }
+
+    #[test]
+    fn batch_starts_a_finished_episode_over_with_the_next_seed() {
+        let count = 3;
+        let mut batch = BatchEnv::new(EnvConfig::default(), count).unwrap();
+        batch.reset(100);
+
+        let mut results = Vec::new();
+        for direction in INTO_THE_BORDER {
+            results = batch.step(&[direction; 3]);
+        }
+
+        let mut single = SnakeEnv::new(EnvConfig::default()).unwrap();
+        for (index, result) in results.iter().enumerate() {
+            assert!(result.done);
+            assert_eq!(result.info.death, Some(DeathCause::Border));
+            assert_eq!(result.observation, single.reset(100 + (index + count) as u64));
+        }
+        assert!(batch.envs().all(|env| !env.is_done() && env.sim().ticks() == 0));
+    }
+
+    #[test]
+    fn walls_of_a_level_show_up_and_kill() {
+        let wall = Position { x: 3, y: 5 };
+        let config = EnvConfig {
+            layout: Layout {
+                walls: [wall].into(),
+                ..Layout::default()
+            },
+            ..EnvConfig::default()
+        };
+        let mut env = SnakeEnv::new(config.clone()).unwrap();
+
+        let observation = env.reset(1);
+        let (width, height) = (env.sim().width() as usize, env.sim().height() as usize);
+        let index = (4 * height + wall.y as usize) * width + wall.x as usize;
+        assert_eq!(observation.data[index], 1.0);
+        assert_eq!(observation.data[4 * height * width..].iter().sum::<f32>(), 1.0);
+
+        assert!(!env.step(Direction::Up).done);
+        let result = env.step(Direction::Up);
+        assert!(result.done);
+        assert_eq!(result.info.death, Some(DeathCause::Border));
+
+        // Snakes can't start inside a wall
+        let mut blocked = config;
+        blocked.layout.walls.insert(Position { x: 3, y: 2 });
+        assert!(SnakeEnv::new(blocked).is_err());
+    }
+}
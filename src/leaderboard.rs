@@ -0,0 +1,211 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sim::BoardMode,
+    storage::{StorageError, data_dir, load_or_backup, load_ron, save_ron},
+};
+
+// Number of scores kept on the leaderboard
+pub const LEADERBOARD_SIZE: usize = 10;
+
+// A finished game on the leaderboard
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: u32,
+    pub length: u32,
+    pub duration_millis: u64,
+    pub timestamp: u64, // seconds since the unix epoch
+    pub seed: u64,
+    pub mode: String,
+    #[serde(default)]
+    pub difficulty: String,
+}
+
+// Best scores across all sessions, highest first
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    // Location of the leaderboard in the platform data directory
+    pub fn default_path() -> PathBuf {
+        data_dir().join("leaderboard.ron")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let mut leaderboard: Self = load_ron(path)?;
+
+        // Don't trust the order of a hand-edited file
+        leaderboard.entries.sort_by(|a, b| b.score.cmp(&a.score).then(a.timestamp.cmp(&b.timestamp)));
+        leaderboard.entries.truncate(LEADERBOARD_SIZE);
+        Ok(leaderboard)
+    }
+
+    // Load the leaderboard, starting empty if the file is missing or corrupt
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        load_or_backup(path, "leaderboard", |path| Self::load(path))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StorageError> {
+        save_ron(self, path)
+    }
+
+    pub fn best(&self) -> Option<&LeaderboardEntry> {
+        self.entries.first()
+    }
+
+    // Insert a finished game, returns its rank if it made it onto the leaderboard
+    pub fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        // Ties keep the older entry in front
+        let rank = self
+            .entries
+            .iter()
+            .position(|other| entry.score > other.score)
+            .unwrap_or(self.entries.len());
+
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        self.entries.insert(rank, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+// Mode of a game as the leaderboard shows it, like `Classic`, `Wrap-around 2P` or `Classic vs 2 CPU`
+pub fn game_mode(board: BoardMode, humans: usize, rivals: usize) -> String {
+    let mut mode = board.name().to_string();
+    if humans > 1 {
+        mode.push_str(&format!(" {humans}P"));
+    }
+    match rivals {
+        0 => {}
+        1 => mode.push_str(" vs CPU"),
+        _ => mode.push_str(&format!(" vs {rivals} CPU")),
+    }
+    mode
+}
+
+impl LeaderboardEntry {
+    // Calendar date of the entry as YYYY-MM-DD
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_from_days((self.timestamp / 86_400) as i64);
+        format!("{year:04}-{month:02}-{day:02}")
+    }
+
+    // Game duration as M:SS
+    pub fn duration(&self) -> String {
+        let secs = self.duration_millis / 1000;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+// Convert days since the unix epoch into a (year, month, day) date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn entry(name: &str, score: u32, timestamp: u64) -> LeaderboardEntry {
+        LeaderboardEntry {
+            name: name.to_string(),
+            score,
+            length: score + 2,
+            duration_millis: 61_000,
+            timestamp,
+            seed: 7,
+            mode: game_mode(BoardMode::Walled, 1, 0),
+            difficulty: "Normal".to_string(),
+        }
+    }
+
+    #[test]
+    fn scores_are_inserted_by_rank() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(leaderboard.insert(entry("a", 10, 1)), Some(0));
+        assert_eq!(leaderboard.insert(entry("b", 30, 2)), Some(0));
+        assert_eq!(leaderboard.insert(entry("c", 20, 3)), Some(1));
+
+        // Ties keep the older entry in front
+        assert_eq!(leaderboard.insert(entry("d", 20, 4)), Some(2));
+
+        let names: Vec<_> = leaderboard.entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["b", "c", "d", "a"]);
+        assert_eq!(leaderboard.best().unwrap().score, 30);
+    }
+
+    #[test]
+    fn only_the_best_scores_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=LEADERBOARD_SIZE as u32 {
+            leaderboard.insert(entry("x", score * 10, u64::from(score)));
+        }
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+
+        // Lower than or equal to the last one doesn't make it
+        assert_eq!(leaderboard.insert(entry("low", 10, 100)), None);
+        assert_eq!(leaderboard.insert(entry("mid", 55, 100)), Some(5));
+        assert_eq!(leaderboard.entries.len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.entries.last().unwrap().score, 20);
+    }
+
+    #[test]
+    fn leaderboard_survives_a_save() {
+        let path = std::env::temp_dir().join(format!("snake_leaderboard_{}.ron", std::process::id()));
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(entry("Ann", 12, 1_700_000_000));
+        leaderboard.insert(LeaderboardEntry { mode: game_mode(BoardMode::Wrap, 2, 1), ..entry("Player 2", 30, 1_700_000_100) });
+
+        leaderboard.save(&path).unwrap();
+        let loaded = Leaderboard::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), leaderboard);
+    }
+
+    #[test]
+    fn hand_edited_files_are_sorted_and_cut() {
+        let entries: Vec<_> = (0..15).map(|score| entry("x", score, u64::from(score))).collect();
+        let text = ron::to_string(&Leaderboard { entries }).unwrap();
+        let path = std::env::temp_dir().join(format!("snake_leaderboard_edited_{}.ron", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let loaded = Leaderboard::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let scores: Vec<_> = loaded.unwrap().entries.iter().map(|entry| entry.score).collect();
+        assert_eq!(scores, [14, 13, 12, 11, 10, 9, 8, 7, 6, 5]);
+    }
+
+    #[test]
+    fn game_modes() {
+        assert_eq!(game_mode(BoardMode::Walled, 1, 0), "Classic");
+        assert_eq!(game_mode(BoardMode::Wrap, 2, 0), "Wrap-around 2P");
+        assert_eq!(game_mode(BoardMode::Walled, 1, 1), "Classic vs CPU");
+        assert_eq!(game_mode(BoardMode::Walled, 2, 2), "Classic 2P vs 2 CPU");
+    }
+
+    #[test]
+    fn dates_and_durations() {
+        let entry = entry("x", 1, 1_700_000_000);
+        assert_eq!(entry.date(), "2023-11-14");
+        assert_eq!(entry.duration(), "1:01");
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Direction, Position},
+    sim::{Goal, GridConfig, Layout},
+};
+
+// Directory inside the assets that holds the level files
+pub const LEVEL_DIR: &str = "levels";
+
+// Tiles of a map, the head of the snake takes the place of a floor tile
+const WALL: char = '#';
+const FLOOR: char = '.';
+const FOOD_ZONE: char = '+';
+const HEADS: [(char, Direction); 4] = [
+    ('^', Direction::Up),
+    ('v', Direction::Down),
+    ('<', Direction::Left),
+    ('>', Direction::Right),
+];
+
+// Boards the editor can resize to
+pub const MIN_SIZE: i32 = 3;
+pub const MAX_SIZE: i32 = 60;
+
+// A board with walls, a start pose, food zones and a goal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    pub grid: GridConfig,
+    pub layout: Layout,
+    pub path: Option<PathBuf>, // file the level was loaded from or saved to
+}
+
+// What a tile of the map holds besides the snake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Floor,
+    Wall,
+    FoodZone,
+}
+
+// Level file as written by hand, the map is drawn top row first
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    name: String,
+    #[serde(default = "default_length")]
+    length: u32, // of the snake at the start, including the head
+    #[serde(default)]
+    goal: Goal,
+    map: Vec<String>,
+}
+
+fn default_length() -> u32 {
+    GridConfig::default().start_length
+}
+
+impl Level {
+    // Parse the RON text of a level file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: LevelFile = ron::from_str(text).map_err(|err| err.to_string())?;
+        if file.name.trim().is_empty() {
+            return Err("the level has no name".to_string());
+        }
+
+        let height = file.map.len() as i32;
+        let width = file.map.first().map_or(0, |row| row.chars().count()) as i32;
+        if height == 0 || width == 0 {
+            return Err("the map is empty".to_string());
+        }
+
+        let mut layout = Layout {
+            goal: file.goal,
+            ..Layout::default()
+        };
+        let mut start: Option<(Position, Direction, usize, usize)> = None;
+
+        for (row, line) in file.map.iter().enumerate() {
+            let row_width = line.chars().count() as i32;
+            if row_width != width {
+                return Err(format!("row {} is {row_width} tiles wide, the first row {width}", row + 1));
+            }
+
+            for (column, tile) in line.chars().enumerate() {
+                // The first row is the top of the board
+                let pos = Position { x: column as i32, y: height - 1 - row as i32 };
+                match tile {
+                    WALL => {
+                        layout.walls.insert(pos);
+                    }
+                    FLOOR => {}
+                    FOOD_ZONE => {
+                        layout.food_zone.insert(pos);
+                    }
+                    _ => {
+                        let Some(&(_, direction)) = HEADS.iter().find(|&&(head, _)| head == tile) else {
+                            return Err(format!(
+                                "unknown tile `{tile}` in row {}, column {}, expected one of # . + ^ v < >",
+                                row + 1,
+                                column + 1,
+                            ));
+                        };
+                        if let Some((_, _, first_row, first_column)) = start {
+                            return Err(format!(
+                                "a second snake head in row {}, column {}, the first is in row {first_row}, column {first_column}",
+                                row + 1,
+                                column + 1,
+                            ));
+                        }
+                        start = Some((pos, direction, row + 1, column + 1));
+                    }
+                }
+            }
+        }
+
+        let Some((start, start_direction, ..)) = start else {
+            return Err("the map has no snake, mark its head with ^, v, < or >".to_string());
+        };
+        match layout.goal {
+            Goal::Score(0) | Goal::Length(0) | Goal::Survive(0) => {
+                return Err(format!("the goal {:?} is reached right away", layout.goal));
+            }
+            _ => {}
+        }
+
+        let level = Self {
+            name: file.name,
+            grid: GridConfig {
+                width,
+                height,
+                start,
+                start_direction,
+                start_length: file.length,
+            },
+            layout,
+            path: None,
+        };
+        level.validate(1)?;
+        Ok(level)
+    }
+
+    // Read a level file, errors name the file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut level = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Self::parse(&text))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        level.path = Some(path.to_path_buf());
+        Ok(level)
+    }
+
+    // Empty board with the snake in its default start pose
+    pub fn blank(name: impl Into<String>, width: i32, height: i32) -> Self {
+        let mut level = Self {
+            name: name.into(),
+            grid: GridConfig::default(),
+            layout: Layout::default(),
+            path: None,
+        };
+        level.resize(width, height);
+        level
+    }
+
+    // Map rows of the level file, top row first
+    pub fn map(&self) -> Vec<String> {
+        (0..self.grid.height)
+            .rev()
+            .map(|y| {
+                (0..self.grid.width)
+                    .map(|x| {
+                        let pos = Position { x, y };
+                        if pos == self.grid.start {
+                            let direction = self.grid.start_direction;
+                            return HEADS.iter().find(|&&(_, head)| head == direction).map_or(FLOOR, |&(head, _)| head);
+                        }
+                        match self.tile(pos) {
+                            Tile::Floor => FLOOR,
+                            Tile::Wall => WALL,
+                            Tile::FoodZone => FOOD_ZONE,
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Text of the level file
+    pub fn to_ron(&self) -> String {
+        let file = LevelFile {
+            name: self.name.clone(),
+            length: self.grid.start_length,
+            goal: self.layout.goal,
+            map: self.map(),
+        };
+        ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).expect("levels serialize to RON")
+    }
+
+    // Write the level file, only levels that pass the checks of a loaded one
+    pub fn save(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        self.validate(1).map_err(|err| format!("{}: {err}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        }
+
+        fs::write(path, self.to_ron()).map_err(|err| format!("{}: {err}", path.display()))?;
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn tile(&self, pos: Position) -> Tile {
+        if self.layout.walls.contains(&pos) {
+            Tile::Wall
+        } else if self.layout.food_zone.contains(&pos) {
+            Tile::FoodZone
+        } else {
+            Tile::Floor
+        }
+    }
+
+    // Put a tile on the board, positions off the board are ignored
+    pub fn set_tile(&mut self, pos: Position, tile: Tile) {
+        if !self.grid.contains(pos) {
+            return;
+        }
+        self.layout.walls.remove(&pos);
+        self.layout.food_zone.remove(&pos);
+        match tile {
+            Tile::Floor => {}
+            Tile::Wall => {
+                self.layout.walls.insert(pos);
+            }
+            Tile::FoodZone => {
+                self.layout.food_zone.insert(pos);
+            }
+        }
+    }
+
+    // Move the head of the snake, the start tile is always floor
+    pub fn set_start(&mut self, pos: Position, direction: Direction) {
+        if self.grid.contains(pos) {
+            self.set_tile(pos, Tile::Floor);
+            self.grid.start = pos;
+            self.grid.start_direction = direction;
+        }
+    }
+
+    // Change the board size, tiles that fall off are dropped and the snake stays on the board
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.grid.width = width.clamp(MIN_SIZE, MAX_SIZE);
+        self.grid.height = height.clamp(MIN_SIZE, MAX_SIZE);
+
+        let grid = self.grid;
+        self.layout.walls.retain(|&pos| grid.contains(pos));
+        self.layout.food_zone.retain(|&pos| grid.contains(pos));
+        self.grid.start = Position {
+            x: grid.start.x.min(grid.width - 1),
+            y: grid.start.y.min(grid.height - 1),
+        };
+    }
+
+    // File name for a new level, from its name
+    pub fn file_name(&self) -> String {
+        let mut stem = String::new();
+        for c in self.name.trim().chars() {
+            match c {
+                c if c.is_ascii_alphanumeric() => stem.push(c.to_ascii_lowercase()),
+                _ if !stem.is_empty() && !stem.ends_with('_') => stem.push('_'),
+                _ => {}
+            }
+        }
+        let stem = stem.trim_end_matches('_');
+        format!("{}.ron", if stem.is_empty() { "level" } else { stem })
+    }
+
+    // Make sure the starting snakes fit on the board, don't start in a wall and have
+    // floor ahead of them. The level doesn't know if the board wraps, so a snake
+    // heading off the board is refused as well.
+    pub fn validate(&self, players: usize) -> Result<(), String> {
+        self.grid.validate(players)?;
+
+        for player in 0..players {
+            if let Some(pos) = self.grid.start_body(player).find(|pos| self.layout.walls.contains(pos)) {
+                return Err(format!(
+                    "snake {} of length {} runs into the wall at ({}, {}) at the start",
+                    player + 1,
+                    self.grid.start_length,
+                    pos.x,
+                    pos.y,
+                ));
+            }
+
+            let (head, direction) = self.grid.start_pose(player);
+            let next = head.step(direction);
+            if !self.grid.contains(next) {
+                return Err(format!(
+                    "snake {} at ({}, {}) heads {direction:?} off the board at the start",
+                    player + 1,
+                    head.x,
+                    head.y,
+                ));
+            }
+            if self.layout.walls.contains(&next) {
+                return Err(format!(
+                    "snake {} at ({}, {}) heads {direction:?} into the wall at ({}, {}) at the start",
+                    player + 1,
+                    head.x,
+                    head.y,
+                    next.x,
+                    next.y,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Every `.ron` file of a directory in the order of the file names, none when the
+// directory doesn't exist. A single malformed file fails the whole directory.
+pub fn load_levels(dir: impl AsRef<Path>) -> Result<Vec<Level>, String> {
+    let dir = dir.as_ref();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("{}: {err}", dir.display())),
+    };
+
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("{}: {err}", dir.display()))?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "ron"));
+    paths.sort();
+
+    let mut names = HashSet::new();
+    let mut levels = Vec::with_capacity(paths.len());
+    for path in paths {
+        let level = Level::load(&path)?;
+        if !names.insert(level.name.to_lowercase()) {
+            return Err(format!("{}: another level is already called `{}`", path.display(), level.name));
+        }
+        levels.push(level);
+    }
+    Ok(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(goal: &str, map: &[&str]) -> Result<Level, String> {
+        let rows: Vec<String> = map.iter().map(|row| format!("{row:?}")).collect();
+        Level::parse(&format!("(name: \"Test\", goal: {goal}, map: [{}])", rows.join(", ")))
+    }
+
+    #[test]
+    fn map_tiles_become_walls_food_zones_and_the_start() {
+        let level = level("Score(10)", &[
+            "#####",
+            "#+..#",
+            "#.>.#",
+            "#####",
+        ])
+        .unwrap();
+
+        // The first row is the top of the board
+        assert_eq!((level.grid.width, level.grid.height), (5, 4));
+        assert_eq!(level.grid.start, Position { x: 2, y: 1 });
+        assert_eq!(level.grid.start_direction, Direction::Right);
+        assert_eq!(level.grid.start_length, GridConfig::default().start_length);
+        assert_eq!(level.layout.food_zone, HashSet::from([Position { x: 1, y: 2 }]));
+        assert_eq!(level.layout.goal, Goal::Score(10));
+
+        assert_eq!(level.layout.walls.len(), 14);
+        assert_eq!(level.tile(Position { x: 0, y: 3 }), Tile::Wall);
+        assert_eq!(level.tile(Position { x: 4, y: 0 }), Tile::Wall);
+        assert_eq!(level.tile(Position { x: 3, y: 1 }), Tile::Floor);
+    }
+
+    #[test]
+    fn every_head_picks_a_start_direction() {
+        for (head, direction) in HEADS {
+            let level = level("Endless", &[".....", &format!("..{head}.."), "....."]).unwrap();
+            assert_eq!(level.grid.start_direction, direction);
+        }
+    }
+
+    #[test]
+    fn written_levels_read_back_the_same() {
+        let level = level("Length(12)", &["..+..", "#.^.#", "....."]).unwrap();
+        assert_eq!(Level::parse(&level.to_ron()).unwrap(), level);
+    }
+
+    #[test]
+    fn malformed_maps_are_rejected() {
+        let broken = [
+            ("Endless", vec![], "empty"),
+            ("Endless", vec!["...", ".."], "row 2 is 2 tiles wide"),
+            ("Endless", vec!["...", ".x."], "unknown tile `x` in row 2, column 2"),
+            ("Endless", vec!["^..", "..v"], "a second snake head in row 2, column 3"),
+            ("Endless", vec!["...", "..."], "no snake"),
+            ("Score(0)", vec!["...", ".^."], "reached right away"),
+            ("Endless", vec!["...", ".^.", ".#."], "runs into the wall at (1, 0)"),
+            ("Endless", vec![".^."], "does not fit on a 3x1 grid"),
+        ];
+        for (goal, map, message) in broken {
+            let err = level(goal, &map).unwrap_err();
+            assert!(err.contains(message), "{map:?}: {err}");
+        }
+
+        assert!(Level::parse("(name: \" \", map: [\".^.\"])").unwrap_err().contains("no name"));
+        assert!(Level::parse("(name: \"Test\", map: [\".^.\"], size: 3)").is_err());
+        assert!(Level::parse("not a level").is_err());
+    }
+
+    #[test]
+    fn snakes_must_start_facing_open_floor() {
+        let facing = [
+            (vec!["...", ".#.", ".^.", "..."], "heads Up into the wall at (1, 2)"),
+            (vec![".^.", "...", "..."], "heads Up off the board"),
+            (vec!["...", "...", ".v."], "heads Down off the board"),
+        ];
+        for (map, message) in facing {
+            let err = level("Endless", &map).unwrap_err();
+            assert!(err.contains(message), "{map:?}: {err}");
+        }
+
+        // The second snake is mirrored and has to face open floor as well
+        let level = level("Endless", &[".>..#.."]).unwrap();
+        assert!(level.validate(1).is_ok());
+        let err = level.validate(2).unwrap_err();
+        assert!(err.contains("snake 2 at (5, 0) heads Left into the wall at (4, 0)"), "{err}");
+    }
+
+    #[test]
+    fn bundled_levels_load() {
+        let levels = load_levels(Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(LEVEL_DIR)).unwrap();
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|level| level.path.is_some()));
+
+        // Every bundled level can be played in versus
+        for level in &levels {
+            assert_eq!(level.validate(2), Ok(()), "{}", level.name);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+pub mod agent;
+pub mod ai;
+pub mod cli;
+pub mod components;
+pub mod env;
+pub mod leaderboard;
+pub mod level;
+pub mod lockstep;
+pub mod net;
+pub mod replay;
+pub mod resources;
+pub mod settings;
+pub mod sim;
+pub mod spectator;
+pub mod storage;
+pub mod systems;
+pub mod tournament;
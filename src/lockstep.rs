@@ -0,0 +1,785 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Direction,
+    net::{bind_any, clean_name, decode, encode, receive_from, report_send_error, send_lossy, ClientStatus, Session, Snapshot, PROTOCOL_VERSION, TIMEOUT},
+    sim::{GridConfig, Layout, Rules, SnakeSim},
+};
+
+// Ticks the local turns are held back, hides short round trips without rolling back
+pub const INPUT_DELAY: u32 = 2;
+
+// Ticks a peer runs ahead of the last input of the other before it waits for it
+pub const MAX_PREDICTION: u32 = 8;
+
+// Ticks between the game state hashes the peers compare
+pub const HASH_INTERVAL: u32 = 30;
+
+// How often the inputs are sent again while nothing new happens
+const RESEND_INTERVAL: Duration = Duration::from_millis(50);
+
+// Pause between the end of a round and the start of the next one
+const ROUND_BREAK: Duration = Duration::from_secs(3);
+
+// Turns waiting for a tick, like the local direction queue
+const MAX_QUEUED_TURNS: usize = 3;
+
+// Inputs sent in one packet at most
+const MAX_PACKET_INPUTS: usize = 64;
+
+const MAX_PACKET: usize = 64 * 1024;
+
+// Board and rules both peers play by, the host picks them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchConfig {
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub layout: Layout, // of the level, empty on an open board
+    pub seed: u64,      // round n plays seed + n
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PeerMessage {
+    // The host answers with the match it runs
+    Hello {
+        version: u32,
+        name: String,
+        config: Option<MatchConfig>,
+    },
+    // Own inputs from a tick on, everything the peer hasn't acknowledged
+    Inputs {
+        round: u32,
+        start: u32,
+        directions: Vec<Direction>,
+        ack: u32,                 // inputs received from the peer
+        hash: Option<(u32, u64)>, // latest confirmed tick with a hash
+    },
+    Leave,
+}
+
+// Two player game that runs ahead on predicted inputs of the remote player and plays
+// the predicted ticks again when the real inputs turn out different
+pub struct Rollback {
+    local: usize,
+    confirmed: SnakeSim,            // the inputs of both players are known up to its tick
+    current: SnakeSim,              // runs ahead of the confirmed game on predictions
+    inputs: [Vec<Direction>; 2],    // direction of each player per tick, starting with tick 1
+    predicted: VecDeque<Direction>, // remote inputs the ticks after the confirmed one used
+    hashes: BTreeMap<u32, u64>,     // of the confirmed game every HASH_INTERVAL ticks
+    rollbacks: u32,
+}
+
+impl Rollback {
+    pub fn new(sim: SnakeSim, local: usize) -> Self {
+        assert_eq!(sim.players(), 2, "rollback is for two players");
+        Self {
+            local,
+            confirmed: sim.clone(),
+            current: sim,
+            inputs: [Vec::new(), Vec::new()],
+            predicted: VecDeque::new(),
+            hashes: BTreeMap::new(),
+            rollbacks: 0,
+        }
+    }
+
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    // Game including the predicted ticks, for the screen
+    pub fn current(&self) -> &SnakeSim {
+        &self.current
+    }
+
+    // Game both peers agree on
+    pub fn confirmed(&self) -> &SnakeSim {
+        &self.confirmed
+    }
+
+    pub fn inputs(&self, player: usize) -> &[Direction] {
+        &self.inputs[player]
+    }
+
+    pub fn hashes(&self) -> &BTreeMap<u32, u64> {
+        &self.hashes
+    }
+
+    // Times predicted ticks were played again
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    // Input of a player for a tick, the latest known one stands in for inputs that didn't arrive
+    fn input(&self, player: usize, tick: u32) -> Direction {
+        let inputs = &self.inputs[player];
+        inputs
+            .get(tick as usize - 1)
+            .or(inputs.last())
+            .copied()
+            .unwrap_or_else(|| self.confirmed.snake(player).direction())
+    }
+
+    fn directions(&self, tick: u32) -> [Direction; 2] {
+        [self.input(0, tick), self.input(1, tick)]
+    }
+
+    // Input of the local player for the next tick without one
+    pub fn push_local(&mut self, direction: Direction) {
+        self.inputs[self.local].push(direction);
+    }
+
+    // Inputs of the remote player from a tick on, ones that would leave a gap are dropped.
+    // So are those further ahead than a peer can be, they come again with the next packets.
+    pub fn add_remote(&mut self, start: u32, directions: &[Direction]) {
+        let remote = self.remote();
+        let known = self.inputs[remote].len();
+        if start == 0 || start as usize > known + 1 {
+            return;
+        }
+
+        let latest = (self.current.ticks() + MAX_PREDICTION + INPUT_DELAY) as usize;
+        let new = directions.iter().skip(known + 1 - start as usize).take(latest.saturating_sub(known));
+        self.inputs[remote].extend(new);
+        self.confirm();
+    }
+
+    // Step the game by one tick, false while the local input is missing or it is
+    // too far ahead of the remote player
+    pub fn advance(&mut self) -> bool {
+        let tick = self.current.ticks() + 1;
+        if self.current.is_over()
+            || self.inputs[self.local].len() < tick as usize
+            || tick - self.confirmed.ticks() > MAX_PREDICTION
+        {
+            return false;
+        }
+
+        let directions = self.directions(tick);
+        self.predicted.push_back(directions[self.remote()]);
+        self.current.tick(&directions);
+        self.confirm();
+        true
+    }
+
+    // Bring the confirmed game up to the known inputs and play the predicted ticks
+    // again when a prediction was wrong
+    fn confirm(&mut self) {
+        let remote = self.remote();
+        let mut mispredicted = false;
+
+        while self.confirmed.ticks() < self.current.ticks() && !self.confirmed.is_over() {
+            let tick = self.confirmed.ticks() + 1;
+            if self.inputs[remote].len() < tick as usize {
+                break;
+            }
+
+            let directions = self.directions(tick);
+            mispredicted |= self.predicted.pop_front() != Some(directions[remote]);
+            self.confirmed.tick(&directions);
+
+            if tick.is_multiple_of(HASH_INTERVAL) {
+                self.hashes.insert(tick, self.confirmed.state_hash());
+            }
+        }
+
+        if mispredicted {
+            self.rollbacks += 1;
+            let target = self.current.ticks();
+            self.current = self.confirmed.clone();
+            self.predicted.clear();
+
+            while self.current.ticks() < target && !self.current.is_over() {
+                let directions = self.directions(self.current.ticks() + 1);
+                self.predicted.push_back(directions[remote]);
+                self.current.tick(&directions);
+            }
+        }
+    }
+}
+
+// Head-to-head game with another player over UDP, without a server. The host
+// is the first player and picks the board, the guest is the second.
+pub struct PeerSession {
+    socket: UdpSocket,
+    local: usize,
+    peer: Option<SocketAddr>,
+    names: [String; 2],
+    config: Option<MatchConfig>,
+    round: u32,
+    game: Option<Rollback>,
+    turns: VecDeque<Direction>, // waiting for a tick
+    acked: u32,                 // local inputs the peer has
+    remote_hashes: BTreeMap<u32, u64>,
+    desync: Option<u32>,
+    rejected: Option<String>,
+    peer_left: bool,
+    next_tick: Instant,
+    round_over_at: Option<Instant>,
+    last_heard: Instant,
+    last_sent: Option<Instant>,
+    snapshot: Option<Snapshot>,
+    send_failed: bool, // reported once, the peer times out if it stays unreachable
+}
+
+impl PeerSession {
+    // Wait for a guest on a local address
+    pub fn host(addr: impl ToSocketAddrs, name: impl Into<String>, config: MatchConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::new(socket, 0, None, name.into(), Some(config)))
+    }
+
+    // Join the game of a host
+    pub fn join(addr: impl ToSocketAddrs, name: impl Into<String>) -> io::Result<Self> {
+        let host = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no host address"))?;
+
+        let mut session = Self::new(bind_any(host)?, 1, Some(host), name.into(), None);
+        session.send(Instant::now())?;
+        Ok(session)
+    }
+
+    fn new(socket: UdpSocket, local: usize, peer: Option<SocketAddr>, name: String, config: Option<MatchConfig>) -> Self {
+        let now = Instant::now();
+        let mut names = [String::new(), String::new()];
+        names[local] = name;
+
+        Self {
+            socket,
+            local,
+            peer,
+            names,
+            config,
+            round: 0,
+            game: None,
+            turns: VecDeque::new(),
+            acked: 0,
+            remote_hashes: BTreeMap::new(),
+            desync: None,
+            rejected: None,
+            peer_left: false,
+            next_tick: now,
+            round_over_at: None,
+            last_heard: now,
+            last_sent: None,
+            snapshot: None,
+            send_failed: false,
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // Game of the current round
+    pub fn game(&self) -> Option<&Rollback> {
+        self.game.as_ref()
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    fn receive(&mut self, now: Instant) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_PACKET];
+        // The host listens to anyone until the first player says hello
+        while let Some((len, addr)) = receive_from(&self.socket, &mut buffer, self.peer)? {
+            if let Some(message) = decode(&buffer[..len]) {
+                self.handle(message, addr, now);
+            }
+        }
+        Ok(())
+    }
+
+    // A hello that can't be answered doesn't stop the packets after it
+    fn handle(&mut self, message: PeerMessage, addr: SocketAddr, now: Instant) {
+        // Only a hello can pick the peer, after that it is the only one listened to
+        match self.peer {
+            Some(peer) if peer != addr => return,
+            None if !matches!(message, PeerMessage::Hello { .. }) => return,
+            _ => {}
+        }
+
+        match message {
+            PeerMessage::Hello { version, name, config } => {
+                if version != PROTOCOL_VERSION {
+                    if self.local == 0 {
+                        // A host tells the guest its version and keeps waiting for another one
+                        let hello = self.hello();
+                        if let Err(err) = send_lossy(&self.socket, &encode(&hello), addr) {
+                            report_send_error(&mut false, addr, &err);
+                        }
+                    } else {
+                        self.rejected = Some(format!("peer speaks protocol {version}, we speak {PROTOCOL_VERSION}"));
+                    }
+                    return;
+                }
+
+                self.peer = Some(addr);
+                self.last_heard = now;
+                self.names[1 - self.local] = clean_name(&name).unwrap_or_else(|| format!("Player {}", 2 - self.local));
+                if self.local == 0 {
+                    // Answered again whenever the answer got lost
+                    let hello = self.hello();
+                    if let Err(err) = send_lossy(&self.socket, &encode(&hello), addr) {
+                        report_send_error(&mut self.send_failed, addr, &err);
+                    }
+                } else if self.config.is_none()
+                    && let Some(config) = config
+                {
+                    match config.grid.validate(2) {
+                        Ok(()) => self.config = Some(config),
+                        Err(err) => self.rejected = Some(format!("the host picked a board that can't be played: {err}")),
+                    }
+                }
+            }
+            PeerMessage::Inputs { round, start, directions, ack, hash } => {
+                self.last_heard = now;
+                if round != self.round {
+                    return;
+                }
+                if let Some(game) = &mut self.game {
+                    game.add_remote(start, &directions);
+                }
+                self.acked = self.acked.max(ack);
+                if let Some((tick, hash)) = hash {
+                    self.remote_hashes.insert(tick, hash);
+                }
+            }
+            PeerMessage::Leave => self.peer_left = true,
+        }
+    }
+
+    fn hello(&self) -> PeerMessage {
+        PeerMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: self.names[self.local].clone(),
+            config: self.config.clone().filter(|_| self.local == 0),
+        }
+    }
+
+    fn start_round(&mut self, config: MatchConfig, now: Instant) {
+        self.round += 1;
+        let seed = config.seed.wrapping_add(u64::from(self.round));
+        let sim = SnakeSim::with_players(config.grid, config.rules, seed, 2).with_layout(config.layout);
+        let heading = sim.snake(self.local).direction();
+
+        let mut game = Rollback::new(sim, self.local);
+        for _ in 0..INPUT_DELAY {
+            game.push_local(heading);
+        }
+
+        self.next_tick = now + Duration::from_millis(u64::from(game.current().tick_millis()));
+        self.game = Some(game);
+        self.turns.clear();
+        self.acked = 0;
+        self.remote_hashes.clear();
+    }
+
+    // Step the game on the clock of the tick speed, waiting for the peer when too far ahead
+    fn tick(&mut self, now: Instant) {
+        let Some(game) = &mut self.game else {
+            return;
+        };
+
+        // Catch up on a slow frame, but not on a long wait for the peer
+        for _ in 0..2 {
+            if now < self.next_tick {
+                break;
+            }
+
+            let tick = game.current().ticks() + 1;
+            while game.inputs(self.local).len() < (tick + INPUT_DELAY) as usize {
+                let last = *game.inputs(self.local).last().expect("the delay is filled with inputs");
+                game.push_local(self.turns.pop_front().unwrap_or(last));
+            }
+
+            if game.advance() {
+                self.next_tick += Duration::from_millis(u64::from(game.current().tick_millis()));
+            } else {
+                self.next_tick = now;
+                break;
+            }
+        }
+    }
+
+    // Compare the hashes both peers have for the same tick
+    fn check_hashes(&mut self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        let hashes = game.hashes();
+
+        self.remote_hashes.retain(|tick, remote| match hashes.get(tick) {
+            Some(local) => {
+                if local != remote && self.desync.is_none_or(|desync| *tick < desync) {
+                    self.desync = Some(*tick);
+                }
+                false
+            }
+            None => true,
+        });
+    }
+
+    fn send(&mut self, now: Instant) -> io::Result<()> {
+        let Some(peer) = self.peer else {
+            return Ok(());
+        };
+
+        let message = match (&self.game, &self.config) {
+            (Some(game), _) => {
+                let inputs = game.inputs(self.local);
+                let start = (self.acked as usize).min(inputs.len());
+                let end = inputs.len().min(start + MAX_PACKET_INPUTS);
+
+                PeerMessage::Inputs {
+                    round: self.round,
+                    start: start as u32 + 1,
+                    directions: inputs[start..end].to_vec(),
+                    ack: game.inputs(1 - self.local).len() as u32,
+                    hash: game.hashes().last_key_value().map(|(&tick, &hash)| (tick, hash)),
+                }
+            }
+            // The guest says hello until the host answers
+            (None, None) => self.hello(),
+            (None, Some(_)) => return Ok(()),
+        };
+
+        self.last_sent = Some(now);
+        send_lossy(&self.socket, &encode(&message), peer)
+    }
+
+    fn update_snapshot(&mut self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        if self
+            .snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.round == self.round && snapshot.tick == game.current().ticks() && !game.confirmed().is_over())
+        {
+            return;
+        }
+
+        let slots = [(Some(0), self.names[0].clone()), (Some(1), self.names[1].clone())];
+        let mut snapshot = Snapshot::of_sim(self.round, game.current(), &slots, self.names.to_vec());
+        // The round is only over once both peers agree on it
+        snapshot.over = game.confirmed().is_over();
+        snapshot.winner = game.confirmed().winner();
+        self.snapshot = Some(snapshot);
+    }
+}
+
+impl Session for PeerSession {
+    fn remote(&self) -> String {
+        match (self.peer, self.socket.local_addr()) {
+            (Some(peer), _) => peer.to_string(),
+            (None, Ok(local)) => format!("a player on port {}", local.port()),
+            (None, Err(_)) => "a player".to_string(),
+        }
+    }
+
+    fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    fn status(&self, now: Instant) -> ClientStatus {
+        if let Some(reason) = &self.rejected {
+            return ClientStatus::Rejected(reason.clone());
+        }
+        if self.peer_left {
+            return ClientStatus::PeerLeft;
+        }
+        if let Some(tick) = self.desync {
+            return ClientStatus::Desynced(tick);
+        }
+        let Some(game) = &self.game else {
+            return ClientStatus::Connecting;
+        };
+        if now.duration_since(self.last_heard) >= TIMEOUT {
+            return ClientStatus::TimedOut;
+        }
+
+        if game.confirmed().is_over() { ClientStatus::Waiting } else { ClientStatus::Playing(self.local) }
+    }
+
+    fn heading(&self) -> Option<Direction> {
+        let game = self.game.as_ref()?;
+        self.turns.back().or(game.inputs(self.local).last()).copied()
+    }
+
+    // The turn waits for the next tick without a turn
+    fn steer(&mut self, direction: Direction, _now: Instant) -> io::Result<bool> {
+        let Some(heading) = self.heading() else {
+            return Ok(false);
+        };
+        if self.turns.len() >= MAX_QUEUED_TURNS || direction == heading || direction == heading.opposite() {
+            return Ok(false);
+        }
+
+        self.turns.push_back(direction);
+        Ok(true)
+    }
+
+    fn update(&mut self, now: Instant) -> io::Result<()> {
+        self.receive(now)?;
+        if self.peer_left || self.desync.is_some() || self.rejected.is_some() {
+            return Ok(());
+        }
+
+        if self.round_over_at.is_some_and(|at| now >= at) {
+            self.game = None;
+            self.round_over_at = None;
+        }
+        if self.game.is_none()
+            && self.peer.is_some()
+            && let Some(config) = self.config.clone()
+        {
+            self.start_round(config, now);
+        }
+
+        let ticks = self.game.as_ref().map(|game| game.current().ticks());
+        self.tick(now);
+        self.check_hashes();
+        self.update_snapshot();
+
+        if let Some(game) = &self.game
+            && game.confirmed().is_over()
+            && self.round_over_at.is_none()
+        {
+            self.round_over_at = Some(now + ROUND_BREAK);
+        }
+
+        let ticked = self.game.as_ref().map(|game| game.current().ticks()) != ticks;
+        if ticked || self.last_sent.is_none_or(|sent| now.duration_since(sent) >= RESEND_INTERVAL) {
+            self.send(now)?;
+        }
+        Ok(())
+    }
+
+    // Best effort, a lost goodbye ends in a time out on the other side
+    fn leave(&mut self) -> io::Result<()> {
+        match self.peer {
+            Some(peer) => send_lossy(&self.socket, &encode(&PeerMessage::Leave), peer),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::Position, net::pump_until, sim::BoardMode};
+
+    fn sim(seed: u64) -> SnakeSim {
+        let rules = Rules { board: BoardMode::Wrap, ..Rules::default() };
+        SnakeSim::with_players(GridConfig::default(), rules, seed, 2)
+    }
+
+    // Inputs for ticks 1 to `ticks`: the first snake goes straight, the second one
+    // steps aside to the left and back to the right now and then
+    fn script(sim: &SnakeSim, ticks: u32) -> [Vec<Direction>; 2] {
+        let (first, second) = (sim.snake(0).direction(), sim.snake(1).direction());
+        let aside = |tick: u32| match tick % 40 {
+            10 | 11 => second.turn_left(),
+            30 | 31 => second.turn_right(),
+            _ => second,
+        };
+        [vec![first; ticks as usize], (1..=ticks).map(aside).collect()]
+    }
+
+    // The same game played with every input known up front
+    fn reference(mut sim: SnakeSim, inputs: &[Vec<Direction>; 2]) -> (SnakeSim, BTreeMap<u32, u64>) {
+        let mut hashes = BTreeMap::new();
+        for (&first, &second) in inputs[0].iter().zip(&inputs[1]) {
+            sim.tick(&[first, second]);
+            if sim.ticks().is_multiple_of(HASH_INTERVAL) {
+                hashes.insert(sim.ticks(), sim.state_hash());
+            }
+        }
+        (sim, hashes)
+    }
+
+    #[test]
+    fn wrong_predictions_are_played_again() {
+        let start = sim(5);
+        let inputs = script(&start, 12);
+        let mut game = Rollback::new(start.clone(), 0);
+        inputs[0].iter().for_each(|&direction| game.push_local(direction));
+
+        // Without remote inputs the game runs ahead up to the prediction limit
+        for _ in 0..MAX_PREDICTION {
+            assert!(game.advance());
+        }
+        assert!(!game.advance());
+        assert_eq!(game.confirmed().ticks(), 0);
+
+        // The remote snake went straight like predicted
+        game.add_remote(1, &inputs[1][..9]);
+        assert_eq!(game.confirmed().ticks(), MAX_PREDICTION);
+        assert_eq!(game.rollbacks(), 0);
+
+        // It turned while the game ran ahead on the straight prediction
+        for _ in 0..4 {
+            assert!(game.advance());
+        }
+        assert_eq!(game.confirmed().ticks(), 9);
+        let predicted = game.current().state_hash();
+        game.add_remote(10, &inputs[1][9..]);
+
+        let (expected, _) = reference(start, &inputs);
+        assert_eq!(game.rollbacks(), 1);
+        assert_ne!(predicted, expected.state_hash());
+        assert_eq!(game.current().state_hash(), expected.state_hash());
+        assert_eq!(game.confirmed().state_hash(), expected.state_hash());
+    }
+
+    #[test]
+    fn rolled_back_game_ends_like_one_without_predictions() {
+        let ticks = 3 * HASH_INTERVAL;
+        let start = sim(11);
+        let inputs = script(&start, ticks);
+        let mut game = Rollback::new(start.clone(), 0);
+        inputs[0].iter().for_each(|&direction| game.push_local(direction));
+
+        // The remote inputs arrive in bursts, a few ticks late
+        for tick in 1..=ticks {
+            assert!(game.advance(), "stalled at tick {tick}");
+            if tick % 4 == 0 {
+                let known = game.inputs(1).len();
+                let until = tick as usize - 3;
+                game.add_remote(known as u32 + 1, &inputs[1][known..until]);
+            }
+        }
+        let known = game.inputs(1).len();
+        game.add_remote(known as u32 + 1, &inputs[1][known..]);
+
+        let (expected, hashes) = reference(start, &inputs);
+        assert!(!expected.is_over());
+        assert!(game.rollbacks() > 0);
+        assert_eq!(game.confirmed().ticks(), ticks);
+        assert_eq!(game.current().state_hash(), expected.state_hash());
+        assert_eq!(game.confirmed().state_hash(), expected.state_hash());
+        assert_eq!(*game.hashes(), hashes);
+    }
+
+    #[test]
+    fn remote_inputs_far_ahead_are_dropped() {
+        let start = sim(3);
+        let mut game = Rollback::new(start.clone(), 0);
+        let straight = start.snake(1).direction();
+
+        game.add_remote(1, &vec![straight; 10_000]);
+        assert_eq!(game.inputs(1).len(), (MAX_PREDICTION + INPUT_DELAY) as usize);
+
+        // The window moves on with the game
+        game.push_local(start.snake(0).direction());
+        assert!(game.advance());
+        game.add_remote(1, &vec![straight; 10_000]);
+        assert_eq!(game.inputs(1).len(), (1 + MAX_PREDICTION + INPUT_DELAY) as usize);
+    }
+
+    fn config() -> MatchConfig {
+        MatchConfig {
+            grid: GridConfig::default(),
+            rules: Rules::default(),
+            layout: Layout::default(),
+            seed: 3,
+        }
+    }
+
+    #[test]
+    fn mismatched_hashes_are_a_desync() {
+        let mut host = PeerSession::host("127.0.0.1:0", "Ann", config()).unwrap();
+        let start = sim(2);
+        let inputs = script(&start, HASH_INTERVAL);
+        let mut game = Rollback::new(start, 0);
+        inputs[0].iter().for_each(|&direction| game.push_local(direction));
+        for _ in 0..HASH_INTERVAL {
+            let known = game.inputs(1).len();
+            game.add_remote(known as u32 + 1, &inputs[1][known..]);
+            assert!(game.advance());
+        }
+        let hash = game.hashes()[&HASH_INTERVAL];
+        host.game = Some(game);
+
+        // Agreeing hashes are checked off
+        host.remote_hashes.insert(HASH_INTERVAL, hash);
+        host.check_hashes();
+        assert!(host.remote_hashes.is_empty());
+        assert_eq!(host.desync, None);
+
+        // Hashes for ticks not confirmed yet wait
+        host.remote_hashes.insert(2 * HASH_INTERVAL, 0);
+        host.remote_hashes.insert(HASH_INTERVAL, hash ^ 1);
+        host.check_hashes();
+        assert_eq!(host.desync, Some(HASH_INTERVAL));
+        assert_eq!(host.remote_hashes.len(), 1);
+        assert_eq!(host.status(Instant::now()), ClientStatus::Desynced(HASH_INTERVAL));
+    }
+
+    fn pump(host: &mut PeerSession, guest: &mut PeerSession, mut done: impl FnMut(&PeerSession, &PeerSession) -> bool) {
+        let update = |(host, guest): &mut (&mut PeerSession, &mut PeerSession), now| {
+            host.update(now).unwrap();
+            guest.update(now).unwrap();
+        };
+        pump_until(&mut (host, guest), update, |(host, guest)| done(host, guest));
+    }
+
+    #[test]
+    fn host_pins_the_first_guest_of_its_version() {
+        let wall = Position { x: 7, y: 7 };
+        let config = MatchConfig {
+            layout: Layout { walls: [wall].into(), ..Layout::default() },
+            ..config()
+        };
+        let mut host = PeerSession::host("127.0.0.1:0", "Ann", config.clone()).unwrap();
+        let addr = host.local_addr().unwrap();
+
+        // Strangers can't end the game before anyone joined, another version gets told off
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stranger.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        stranger.send_to(&encode(&PeerMessage::Leave), addr).unwrap();
+        let old = PeerMessage::Hello { version: PROTOCOL_VERSION - 1, name: "Old".to_string(), config: None };
+        stranger.send_to(&encode(&old), addr).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        host.update(Instant::now()).unwrap();
+
+        let mut buffer = vec![0; MAX_PACKET];
+        let (len, _) = stranger.recv_from(&mut buffer).unwrap();
+        assert!(matches!(decode(&buffer[..len]), Some(PeerMessage::Hello { version: PROTOCOL_VERSION, .. })));
+        assert_eq!(host.peer, None);
+        assert_eq!(host.status(Instant::now()), ClientStatus::Connecting);
+
+        // The guest plays the level of the host
+        let mut guest = PeerSession::join(addr, "Bob\n").unwrap();
+        pump(&mut host, &mut guest, |host, guest| host.game.is_some() && guest.game.is_some());
+        assert_eq!(guest.config, Some(config));
+        assert!(guest.game().unwrap().current().is_wall(wall));
+        assert_eq!(host.names, ["Ann", "Bob"]);
+
+        // Nobody else gets through once the guest is pinned
+        stranger.send_to(&encode(&PeerMessage::Leave), addr).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        host.update(Instant::now()).unwrap();
+        assert!(!host.peer_left);
+
+        guest.leave().unwrap();
+        pump(&mut host, &mut guest, |host, _| host.peer_left);
+        assert_eq!(host.status(Instant::now()), ClientStatus::PeerLeft);
+    }
+}
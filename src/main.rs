@@ -1,20 +1,29 @@
-use bevy::prelude::*;
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
 
-mod components;
-mod resources;
-mod systems;
-
-use systems::{
-    input::input,
+use snakegame::systems::{
+    ai::ai_input,
+    autopilot::{autopilot_controls, apply_autopilot, update_path_overlay},
+    input::{input, relative_input},
+    controls::{
+        setup_controls_screen,
+        cleanup_controls_screen,
+        binding_button_system,
+        capture_binding,
+        update_binding_texts,
+    },
+    gamepad::{gamepad_input, gamepad_menu_navigation},
+    pointer::pointer_input,
     movement::snake_movement,
+    network::NetworkPlugin,
     collision::{snake_collision, snake_growth},
     spawn::food_spawner,
+    spectator::SpectatorPlugin,
     rendering::{
         position_translation,
         size_scaling,
         setup_camera,
-        spawn_borders,
     },
+    editor::EditorPlugin,
     state::{
         setup_main_menu,
         cleanup_main_menu,
@@ -25,54 +34,251 @@ use systems::{
         update_score_ui,
         reset_game,
         handle_game_over,
+        restart_controls,
         menu_button_system,
+        update_menu_texts,
+        game_running,
     },
+    leaderboard::record_score,
+    pause::{PausePlugin, counting_down, cleanup_countdown},
+    replay::ReplayPlugin,
 };
 
-use resources::{MovementTimer, FoodSpawnTimer, SnakeSegments, LastTailPosition, DirectionLocked, GrowthEvent, GameOverEvent, NextHeadPosition, GameState, Score, HighScore};
+use snakegame::resources::{MovementTimer, SnakeSegments, PlayerCount, Rivals, Autopilot, GrowthEvent, GameOverEvent, TickOutcome, GameState, Score, HighScores, PlayerName, GameSeed, GameRules, ReplayRecorder, CurrentReplay, ResumeCountdown, GameSettings, PendingBinding, RestartGame, Network, Spectators, Levels};
+
+use snakegame::{agent::AgentRegistry, cli::CliArgs, lockstep::{MatchConfig, PeerSession}, net::{NetClient, Session, DEFAULT_PORT}, leaderboard::Leaderboard, level::{load_levels, LEVEL_DIR}, settings::Settings, replay::{Playback, Replay}, sim::{BoardMode, GridConfig, Layout, SpeedCurve}, spectator::SpectatorServer};
 
 
 
 fn main() {
-    App::new()
+    let cli = CliArgs::from_env().unwrap_or_else(|err| {
+        eprintln!("snakegame: {err}");
+        std::process::exit(2);
+    });
+
+    // A seed on the command line wins over the one of the settings
+    let settings = GameSettings::load(Settings::default_path());
+    let seed = cli.seed.or(settings.settings.seed);
+
+    // Start straight into the playback when a replay file is given
+    let replay = cli.replay.as_ref().map(|path| {
+        Replay::load(path).map(Playback::new).unwrap_or_else(|err| {
+            eprintln!("snakegame: could not load replay {}: {err}", path.display());
+            std::process::exit(2);
+        })
+    });
+    // Board for new games
+    let mut grid_config = GridConfig::default();
+    if let Some((width, height)) = cli.grid {
+        grid_config.width = width;
+        grid_config.height = height;
+    }
+    if let Some(length) = cli.length {
+        grid_config.start_length = length;
+    }
+    let players = cli.players.unwrap_or(1);
+    let rivals = Rivals {
+        count: cli.rivals.unwrap_or(0),
+        skill: cli.ai_skill.unwrap_or_default(),
+    };
+    if let Err(err) = grid_config.validate(players + rivals.count) {
+        eprintln!("snakegame: {err}");
+        std::process::exit(2);
+    }
+
+    // Levels from the assets, a broken level file stops the game with the reason
+    let level_dir = FileAssetReader::get_base_path().join("assets").join(LEVEL_DIR);
+    let mut levels = Levels {
+        dir: level_dir.clone(),
+        levels: load_levels(&level_dir).unwrap_or_else(|err| {
+            eprintln!("snakegame: {err}");
+            std::process::exit(2);
+        }),
+        selected: None,
+        playtest: None,
+    };
+    if let Some(name) = &cli.level {
+        levels.selected = levels.levels.iter().position(|level| level.name.eq_ignore_ascii_case(name));
+        if levels.selected.is_none() {
+            let names: Vec<_> = levels.levels.iter().map(|level| level.name.as_str()).collect();
+            eprintln!("snakegame: unknown level `{name}`, available levels: {}", names.join(", "));
+            std::process::exit(2);
+        }
+    }
+
+    // Agent that steers instead of the keyboard
+    let agents = AgentRegistry::default();
+    let mut autopilot = Autopilot::default();
+    if let Some(name) = &cli.agent {
+        if !agents.contains(name) {
+            let names: Vec<_> = agents.names().collect();
+            eprintln!("snakegame: unknown agent `{name}`, available agents: {}", names.join(", "));
+            std::process::exit(2);
+        }
+        autopilot.enabled = true;
+        autopilot.agent = name.clone();
+    }
+
+    let mut rules = cli.difficulty.unwrap_or_default().rules();
+    if cli.wrap {
+        rules.board = BoardMode::Wrap;
+    }
+    // The curve of the command line, the settings or the difficulty, with the parameters of the command line
+    if let Some(curve) = settings.settings.speed_curve {
+        rules.speed_curve = curve;
+    }
+    rules.speed_curve = cli
+        .speedup
+        .as_ref()
+        .map_or(Ok(rules.speed_curve), |name| SpeedCurve::with_name(name, rules.tick_millis))
+        .and_then(|curve| curve.with_params(cli.speedup_params))
+        .unwrap_or_else(|err| {
+            eprintln!("snakegame: {err}");
+            std::process::exit(2);
+        });
+
+    // Name for the leaderboard, defaults to the user name of the OS
+    let player_name = cli.name.clone()
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "Player".to_string());
+
+    // Join a game server instead of playing locally, the port is optional
+    let with_port = |addr: &str| if addr.contains(':') { addr.to_string() } else { format!("{addr}:{DEFAULT_PORT}") };
+    let network: Option<Box<dyn Session>> = if let Some(addr) = &cli.connect {
+        let client = NetClient::connect(with_port(addr).as_str(), player_name.clone()).unwrap_or_else(|err| {
+            eprintln!("snakegame: could not connect to {addr}: {err}");
+            std::process::exit(2);
+        });
+        Some(Box::new(client))
+    } else if let Some(port) = cli.host {
+        // Head-to-head with one other player, on the level or the board and rules of the command line
+        let (grid, layout, valid) = match levels.current() {
+            Some(level) => (level.grid, level.layout.clone(), level.validate(2)),
+            None => (grid_config, Layout::default(), grid_config.validate(2)),
+        };
+        if let Err(err) = valid {
+            eprintln!("snakegame: {err}");
+            std::process::exit(2);
+        }
+        let config = MatchConfig {
+            grid,
+            rules,
+            layout,
+            seed: seed.unwrap_or_else(rand::random),
+        };
+        let session = PeerSession::host(("0.0.0.0", port), player_name.clone(), config).unwrap_or_else(|err| {
+            eprintln!("snakegame: could not listen on port {port}: {err}");
+            std::process::exit(2);
+        });
+        Some(Box::new(session))
+    } else if let Some(addr) = &cli.join {
+        let session = PeerSession::join(with_port(addr).as_str(), player_name.clone()).unwrap_or_else(|err| {
+            eprintln!("snakegame: could not reach {addr}: {err}");
+            std::process::exit(2);
+        });
+        Some(Box::new(session))
+    } else {
+        None
+    };
+
+    // Stream the board to spectators on a local WebSocket endpoint
+    let spectators = cli.spectate.map(|port| {
+        SpectatorServer::bind(("127.0.0.1", port)).unwrap_or_else(|err| {
+            eprintln!("snakegame: could not open the spectator port {port}: {err}");
+            std::process::exit(2);
+        })
+    });
+
+    let initial_state = if network.is_some() {
+        GameState::Online
+    } else if replay.is_some() {
+        GameState::Replay
+    } else {
+        GameState::Menu
+    };
+
+    let mut app = App::new();
+    if let Some(session) = network {
+        app.insert_resource(Network(session));
+    }
+    if let Some(server) = spectators {
+        app.insert_resource(Spectators(server));
+    }
+
+    app
         .add_plugins(DefaultPlugins)
+        .add_plugins((PausePlugin, ReplayPlugin, EditorPlugin, NetworkPlugin, SpectatorPlugin))
 
-        .init_state::<GameState>()
+        .insert_state(initial_state)
 
         .add_message::<GrowthEvent>()
         .add_message::<GameOverEvent>()
-        .add_message::<NextHeadPosition>()
+        .add_message::<TickOutcome>()
+        .add_message::<RestartGame>()
 
         .insert_resource(MovementTimer::default())
-        .insert_resource(FoodSpawnTimer::default())
         .insert_resource(SnakeSegments::default())
-        .insert_resource(LastTailPosition::default())
-        .insert_resource(DirectionLocked::default())
+        .insert_resource(PlayerCount(players))
+        .insert_resource(rivals)
+        .insert_resource(autopilot)
+        .insert_resource(agents)
         .insert_resource(Score::default())
-        .insert_resource(HighScore::default())
+        .insert_resource(HighScores::load(Leaderboard::default_path()))
+        .insert_resource(PlayerName(player_name))
+        .insert_resource(GameSeed::new(seed))
+        .insert_resource(grid_config)
+        .insert_resource(levels)
+        .insert_resource(GameRules(rules))
+        .insert_resource(ReplayRecorder::default())
+        .insert_resource(CurrentReplay(replay))
+        .insert_resource(ResumeCountdown::default())
+        .insert_resource(settings)
+        .insert_resource(PendingBinding::default())
 
         .add_systems(Startup, setup_camera)
 
-        .add_systems(OnEnter(GameState::Menu), setup_main_menu)
+        .add_systems(OnEnter(GameState::Menu), (setup_main_menu, cleanup_score_ui))
         .add_systems(OnExit(GameState::Menu), cleanup_main_menu)
 
-        .add_systems(OnEnter(GameState::GameOver), setup_game_over_screen)
+        .add_systems(OnEnter(GameState::GameOver), (setup_game_over_screen, cleanup_score_ui))
         .add_systems(OnExit(GameState::GameOver), cleanup_game_over_screen)
 
-        .add_systems(OnEnter(GameState::Playing), (reset_game, spawn_borders, setup_score_ui))
-        .add_systems(OnExit(GameState::Playing), cleanup_score_ui)
+        // Coming back from the pause keeps the running game
+        .add_systems(OnEnter(GameState::Playing), (reset_game, setup_score_ui).run_if(not(counting_down)))
+
+        .add_systems(OnEnter(GameState::Controls), setup_controls_screen)
+        .add_systems(OnExit(GameState::Controls), cleanup_controls_screen)
 
         .add_systems(Update, (
             menu_button_system,
-            input.run_if(in_state(GameState::Playing)),
-            snake_movement.run_if(in_state(GameState::Playing)),
-            snake_collision.run_if(in_state(GameState::Playing)),
-            snake_growth.run_if(in_state(GameState::Playing)),
-            food_spawner.run_if(in_state(GameState::Playing)),
-            update_score_ui.run_if(in_state(GameState::Playing)),
-            handle_game_over,
+            update_menu_texts.after(menu_button_system).after(gamepad_menu_navigation),
+            gamepad_menu_navigation.run_if(in_state(GameState::Menu).or(in_state(GameState::GameOver)).or(in_state(GameState::Paused)).or(in_state(GameState::Controls))),
+            restart_controls.run_if(in_state(GameState::Playing).or(in_state(GameState::Paused)).or(in_state(GameState::GameOver))).before(snake_movement),
+            (reset_game, cleanup_countdown).run_if(on_message::<RestartGame>).after(restart_controls).before(snake_movement),
+            (input, relative_input, pointer_input, gamepad_input, ai_input)
+                .run_if(in_state(GameState::Playing))
+                .before(snake_movement),
+            (autopilot_controls, apply_autopilot)
+                .chain()
+                .run_if(in_state(GameState::Playing))
+                .before(ai_input),
+            update_path_overlay.run_if(in_state(GameState::Playing)).after(ai_input),
+            snake_movement.run_if(game_running),
+            snake_collision.run_if(game_running).after(snake_movement),
+            snake_growth.run_if(game_running).after(snake_collision),
+            food_spawner.run_if(game_running).after(snake_movement),
+            update_score_ui.run_if(game_running),
+            handle_game_over.run_if(in_state(GameState::Playing)).after(snake_collision),
+            record_score.run_if(in_state(GameState::Playing)).after(snake_collision),
         ))
 
+        .add_systems(Update, (
+            binding_button_system,
+            capture_binding.after(binding_button_system),
+            update_binding_texts.after(capture_binding),
+        ).run_if(in_state(GameState::Controls)))
+
         .add_systems(PostUpdate, (position_translation, size_scaling))
 
         .run();
@@ -0,0 +1,882 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Direction, Position},
+    sim::{FoodItem, GridConfig, Rules, SnakeSim, MAX_PLAYERS},
+};
+
+// Bumped whenever the messages change, clients of another version are turned away
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const DEFAULT_PORT: u16 = 7878;
+
+// Clients that send nothing for this long have left
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+// How often unanswered joins and unacknowledged inputs are sent again, also the
+// rate of snapshots while no round is running
+const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+
+// Pause between the end of a round and the start of the next one
+const ROUND_BREAK: Duration = Duration::from_secs(3);
+
+// Turns a client queues ahead of the server, like the local direction queue
+const MAX_PENDING_INPUTS: usize = 3;
+
+// Turns the server holds for a client and how far past the current tick they may
+// go, the acknowledged ones that are still due on top of the client's own queue
+const MAX_HELD_INPUTS: usize = 2 * MAX_PENDING_INPUTS;
+
+const MAX_PACKET: usize = 64 * 1024;
+
+// Largest payload of a single UDP datagram, snapshots never grow past it
+const MAX_DATAGRAM: usize = 65_507;
+
+// Names are cut to this many characters, they go into every snapshot
+pub const MAX_NAME_LEN: usize = 16;
+
+// A turn and the tick of the round it is meant for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickInput {
+    pub tick: u32,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join { version: u32, name: String },
+    // Every input the server hasn't acknowledged yet, so lost packets are made up for
+    Input { round: u32, inputs: Vec<TickInput> },
+    Leave,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome { client: u32 },
+    Rejected { reason: String },
+    Snapshot(Snapshot),
+}
+
+// A snake as the clients see it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnakeState {
+    pub client: Option<u32>, // None once its player left
+    pub name: String,
+    pub body: Vec<Position>, // head first
+    pub direction: Direction,
+    pub score: u32,
+    pub alive: bool,
+}
+
+// Full state of the server after a tick, so any snapshot can replace a lost one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub round: u32, // 0 before the first round
+    pub tick: u32,
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub walls: Vec<Position>, // of the level, row by row
+    pub snakes: Vec<SnakeState>,
+    pub food: Vec<FoodItem>,
+    pub lobby: Vec<String>, // names of every connected player
+    pub over: bool,
+    pub winner: Option<usize>,
+    pub ack: u32, // latest input tick received from the client the snapshot is sent to
+}
+
+impl Snapshot {
+    // State of a running game, with the client and name of every snake
+    pub fn of_sim(round: u32, sim: &SnakeSim, slots: &[(Option<u32>, String)], lobby: Vec<String>) -> Self {
+        let snakes = sim
+            .snakes()
+            .iter()
+            .zip(slots)
+            .map(|(snake, (client, name))| SnakeState {
+                client: *client,
+                name: name.clone(),
+                body: snake.body().collect(),
+                direction: snake.direction(),
+                score: snake.score(),
+                alive: snake.is_alive(),
+            })
+            .collect();
+
+        let mut walls: Vec<_> = sim.layout().walls.iter().copied().collect();
+        walls.sort_by_key(|pos| (pos.y, pos.x));
+
+        Self {
+            round,
+            tick: sim.ticks(),
+            grid: *sim.grid(),
+            rules: *sim.rules(),
+            walls,
+            snakes,
+            food: sim.food().to_vec(),
+            lobby,
+            over: sim.is_over(),
+            winner: sim.winner(),
+            ack: 0,
+        }
+    }
+}
+
+pub(crate) fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    bincode::serialize(message).expect("messages serialize")
+}
+
+pub(crate) fn decode<T: for<'de> Deserialize<'de>>(packet: &[u8]) -> Option<T> {
+    bincode::deserialize(packet).ok()
+}
+
+// Name as other players see it, without control characters and cut to
+// MAX_NAME_LEN. None when nothing is left.
+pub fn clean_name(name: &str) -> Option<String> {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+    let name = name.trim_end();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// Rules of the games the server runs
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub min_players: usize, // a round starts once this many players joined
+    pub seed: u64,          // round n plays seed + n
+}
+
+impl ServerConfig {
+    // The snakes have to fit and even a full board has to fit into a single snapshot
+    pub fn validate(&self) -> Result<(), String> {
+        self.grid.validate(self.min_players.clamp(1, MAX_PLAYERS))?;
+
+        let size = encode(&ServerMessage::Snapshot(self.largest_snapshot())).len();
+        if size > MAX_DATAGRAM {
+            return Err(format!(
+                "a {}x{} board with up to {} food doesn't fit into a single packet",
+                self.grid.width, self.grid.height, self.rules.max_food,
+            ));
+        }
+        Ok(())
+    }
+
+    // Snakes covering every tile, as much food as the rules allow and the longest names
+    fn largest_snapshot(&self) -> Snapshot {
+        let tiles = (self.grid.width * self.grid.height).max(0) as usize;
+        let name = "\u{1F40D}".repeat(MAX_NAME_LEN);
+        let snake = SnakeState {
+            client: Some(u32::MAX),
+            name: name.clone(),
+            body: vec![Position { x: 0, y: 0 }; tiles.div_ceil(MAX_PLAYERS)],
+            direction: Direction::Up,
+            score: u32::MAX,
+            alive: true,
+        };
+
+        Snapshot {
+            round: u32::MAX,
+            tick: u32::MAX,
+            grid: self.grid,
+            rules: self.rules,
+            walls: Vec::new(),
+            snakes: vec![snake; MAX_PLAYERS],
+            food: vec![FoodItem { pos: Position { x: 0, y: 0 }, variant: u32::MAX }; tiles.min(self.rules.max_food as usize)],
+            lobby: vec![name; MAX_PLAYERS],
+            over: true,
+            winner: Some(0),
+            ack: u32::MAX,
+        }
+    }
+}
+
+struct RemoteClient {
+    id: u32,
+    addr: SocketAddr,
+    name: String,
+    last_seen: Instant,
+    inputs: BTreeMap<u32, Direction>, // by tick
+    ack: u32,
+    send_failed: bool, // reported once, the client times out if it stays unreachable
+}
+
+// Owns the game, steps it with the inputs of the clients and sends them the result
+pub struct NetServer {
+    socket: UdpSocket,
+    config: ServerConfig,
+    clients: Vec<RemoteClient>,
+    next_client: u32,
+    sim: Option<SnakeSim>,
+    round: u32,
+    slots: Vec<(Option<u32>, String)>, // client and name of every snake of the round
+    next_tick: Instant,
+    next_broadcast: Instant,
+    round_over_at: Option<Instant>,
+}
+
+impl NetServer {
+    pub fn bind(addr: impl ToSocketAddrs, config: ServerConfig) -> io::Result<Self> {
+        config.validate().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        let now = Instant::now();
+
+        Ok(Self {
+            socket,
+            config,
+            clients: Vec::new(),
+            next_client: 1,
+            sim: None,
+            round: 0,
+            slots: Vec::new(),
+            next_tick: now,
+            next_broadcast: now,
+            round_over_at: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    // Game of the current round
+    pub fn sim(&self) -> Option<&SnakeSim> {
+        self.sim.as_ref()
+    }
+
+    // Serve until an error, sleeping between updates
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.update(Instant::now())?;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Handle the packets that arrived, step the game when a tick is due and send snapshots
+    pub fn update(&mut self, now: Instant) -> io::Result<()> {
+        self.receive(now)?;
+
+        // Players that went quiet have left
+        let timed_out: Vec<u32> = self
+            .clients
+            .iter()
+            .filter(|client| now.duration_since(client.last_seen) >= TIMEOUT)
+            .map(|client| client.id)
+            .collect();
+        for id in timed_out {
+            self.remove_client(id);
+        }
+
+        if self.round_over_at.is_some_and(|at| now >= at) {
+            self.sim = None;
+            self.round_over_at = None;
+        }
+        if self.sim.is_none() && self.clients.len() >= self.config.min_players.max(1) {
+            self.start_round(now);
+        }
+
+        let ticked = self.sim.as_ref().is_some_and(|sim| !sim.is_over()) && now >= self.next_tick;
+        if ticked {
+            self.tick(now);
+        }
+        if ticked || now >= self.next_broadcast {
+            self.broadcast(now);
+        }
+
+        Ok(())
+    }
+
+    fn receive(&mut self, now: Instant) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_PACKET];
+        while let Some((len, addr)) = receive_from(&self.socket, &mut buffer, None)? {
+            if let Some(message) = decode(&buffer[..len]) {
+                self.handle(message, addr, now);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, message: ClientMessage, addr: SocketAddr, now: Instant) {
+        let client = self.clients.iter_mut().find(|client| client.addr == addr);
+
+        match (message, client) {
+            // The welcome got lost, send it again
+            (ClientMessage::Join { .. }, Some(client)) => {
+                client.last_seen = now;
+                let welcome = ServerMessage::Welcome { client: client.id };
+                if let Err(err) = send_lossy(&self.socket, &encode(&welcome), addr) {
+                    report_send_error(&mut client.send_failed, addr, &err);
+                }
+            }
+            (ClientMessage::Join { version, name }, None) => {
+                let rejection = if version != PROTOCOL_VERSION {
+                    Some(format!("server speaks protocol {PROTOCOL_VERSION}, client {version}"))
+                } else if self.clients.len() >= MAX_PLAYERS {
+                    Some("server is full".to_string())
+                } else {
+                    None
+                };
+                if let Some(reason) = rejection {
+                    if let Err(err) = send_lossy(&self.socket, &encode(&ServerMessage::Rejected { reason }), addr) {
+                        report_send_error(&mut false, addr, &err);
+                    }
+                    return;
+                }
+
+                let id = self.next_client;
+                self.next_client += 1;
+                let mut client = RemoteClient {
+                    id,
+                    addr,
+                    name: clean_name(&name).unwrap_or_else(|| format!("Player {id}")),
+                    last_seen: now,
+                    inputs: BTreeMap::new(),
+                    ack: 0,
+                    send_failed: false,
+                };
+                if let Err(err) = send_lossy(&self.socket, &encode(&ServerMessage::Welcome { client: id }), addr) {
+                    report_send_error(&mut client.send_failed, addr, &err);
+                }
+                self.clients.push(client);
+            }
+            (ClientMessage::Input { round, inputs }, Some(client)) => {
+                client.last_seen = now;
+                // Only the snakes of the running round steer, clients in the lobby wait for the next one
+                let Some(sim) = self.sim.as_ref().filter(|_| round == self.round) else {
+                    return;
+                };
+                if !self.slots.iter().any(|(id, _)| *id == Some(client.id)) {
+                    return;
+                }
+
+                // Inputs up to the acknowledged tick arrived before, those too far ahead
+                // stay unacknowledged and the client sends them again
+                let ack = client.ack;
+                let latest = sim.ticks().saturating_add(MAX_HELD_INPUTS as u32);
+                for input in inputs.into_iter().filter(|input| input.tick > ack) {
+                    if input.tick > latest || client.inputs.len() >= MAX_HELD_INPUTS {
+                        break;
+                    }
+                    client.inputs.insert(input.tick, input.direction);
+                    client.ack = client.ack.max(input.tick);
+                }
+            }
+            (ClientMessage::Leave, Some(client)) => {
+                let id = client.id;
+                self.remove_client(id);
+            }
+            // Packets of clients that timed out
+            (_, None) => {}
+        }
+    }
+
+    fn remove_client(&mut self, id: u32) {
+        self.clients.retain(|client| client.id != id);
+
+        if let Some(player) = self.slots.iter().position(|(client, _)| *client == Some(id)) {
+            self.slots[player].0 = None;
+            if let Some(sim) = &mut self.sim {
+                sim.forfeit(player);
+            }
+        }
+    }
+
+    fn start_round(&mut self, now: Instant) {
+        let players = self.clients.len().min(MAX_PLAYERS);
+        self.round += 1;
+        self.slots = self.clients[..players]
+            .iter()
+            .map(|client| (Some(client.id), client.name.clone()))
+            .collect();
+        for client in &mut self.clients {
+            client.inputs.clear();
+            client.ack = 0;
+        }
+
+        let seed = self.config.seed.wrapping_add(u64::from(self.round));
+        let sim = SnakeSim::with_players(self.config.grid, self.config.rules, seed, players);
+        self.next_tick = now + Duration::from_millis(u64::from(sim.tick_millis()));
+        self.sim = Some(sim);
+    }
+
+    fn tick(&mut self, now: Instant) {
+        let Some(sim) = &mut self.sim else {
+            return;
+        };
+        let tick = sim.ticks() + 1;
+
+        // One queued turn per snake and tick, late turns still count
+        let directions: Vec<Direction> = self
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(player, (id, _))| {
+                let client = self.clients.iter_mut().find(|client| Some(client.id) == *id);
+                let due = client.and_then(|client| {
+                    let (&first, _) = client.inputs.first_key_value().filter(|&(&first, _)| first <= tick)?;
+                    client.inputs.remove(&first)
+                });
+                due.unwrap_or(sim.snake(player).direction())
+            })
+            .collect();
+
+        sim.tick(&directions);
+        self.next_tick = now + Duration::from_millis(u64::from(sim.tick_millis()));
+
+        if sim.is_over() {
+            self.round_over_at = Some(now + ROUND_BREAK);
+        }
+    }
+
+    // A client that can't be reached doesn't stop the others from getting the snapshot
+    fn broadcast(&mut self, now: Instant) {
+        self.next_broadcast = now + RESEND_INTERVAL;
+        let mut snapshot = self.snapshot();
+
+        for client in &mut self.clients {
+            snapshot.ack = client.ack;
+            let packet = encode(&ServerMessage::Snapshot(snapshot.clone()));
+            if let Err(err) = send_lossy(&self.socket, &packet, client.addr) {
+                report_send_error(&mut client.send_failed, client.addr, &err);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let lobby = self.clients.iter().map(|client| client.name.clone()).collect();
+        let Some(sim) = &self.sim else {
+            return Snapshot {
+                round: self.round,
+                tick: 0,
+                grid: self.config.grid,
+                rules: self.config.rules,
+                walls: Vec::new(),
+                snakes: Vec::new(),
+                food: Vec::new(),
+                lobby,
+                over: self.round > 0,
+                winner: None,
+                ack: 0,
+            };
+        };
+
+        Snapshot::of_sim(self.round, sim, &self.slots, lobby)
+    }
+}
+
+// Where a client stands with the server or its peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientStatus {
+    Connecting,
+    Rejected(String),
+    TimedOut,
+    PeerLeft,
+    Desynced(u32), // tick the game states of two peers first differed at
+    Waiting, // for the next round
+    Playing(usize), // index of the own snake
+}
+
+// An online game seen from one player, the same screen shows every kind
+pub trait Session: Send + Sync {
+    // The server or peer, for messages
+    fn remote(&self) -> String;
+
+    fn snapshot(&self) -> Option<&Snapshot>;
+
+    fn status(&self, now: Instant) -> ClientStatus;
+
+    // Heading of the own snake once the queued turns are applied
+    fn heading(&self) -> Option<Direction>;
+
+    // Queue a turn, returns false if it was dropped
+    fn steer(&mut self, direction: Direction, now: Instant) -> io::Result<bool>;
+
+    // Handle the packets that arrived and send what is due
+    fn update(&mut self, now: Instant) -> io::Result<()>;
+
+    // Tell the other side right away instead of waiting for the timeout
+    fn leave(&mut self) -> io::Result<()>;
+}
+
+// Sends the turns of the player to the server and keeps its latest snapshot
+pub struct NetClient {
+    socket: UdpSocket,
+    server: SocketAddr,
+    name: String,
+    id: Option<u32>,
+    rejected: Option<String>,
+    snapshot: Option<Snapshot>,
+    pending: Vec<TickInput>, // not acknowledged yet
+    last_tick: u32,          // of the latest queued input
+    last_heard: Instant,
+    last_sent: Option<Instant>,
+}
+
+impl NetClient {
+    pub fn connect(server: impl ToSocketAddrs, name: impl Into<String>) -> io::Result<Self> {
+        let server = server
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no server address"))?;
+
+        let mut client = Self {
+            socket: bind_any(server)?,
+            server,
+            name: name.into(),
+            id: None,
+            rejected: None,
+            snapshot: None,
+            pending: Vec::new(),
+            last_tick: 0,
+            last_heard: Instant::now(),
+            last_sent: None,
+        };
+        client.update(Instant::now())?;
+        Ok(client)
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    fn handle(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::Welcome { client } => self.id = Some(client),
+            ServerMessage::Rejected { reason } => self.rejected = Some(reason),
+            ServerMessage::Snapshot(snapshot) => {
+                // Packets can arrive out of order
+                let current = self.snapshot.as_ref().map(|current| (current.round, current.tick));
+                if current.is_some_and(|current| (snapshot.round, snapshot.tick) < current) {
+                    return;
+                }
+                if current.is_some_and(|(round, _)| round != snapshot.round) {
+                    self.pending.clear();
+                    self.last_tick = 0;
+                }
+
+                self.pending.retain(|input| input.tick > snapshot.ack);
+                self.snapshot = Some(snapshot);
+            }
+        }
+    }
+
+    fn send(&mut self, now: Instant) -> io::Result<()> {
+        if self.rejected.is_some() {
+            return Ok(());
+        }
+        let message = match (self.id, &self.snapshot) {
+            (Some(_), Some(snapshot)) => ClientMessage::Input {
+                round: snapshot.round,
+                inputs: self.pending.clone(),
+            },
+            _ => ClientMessage::Join {
+                version: PROTOCOL_VERSION,
+                name: self.name.clone(),
+            },
+        };
+
+        self.last_sent = Some(now);
+        send_lossy(&self.socket, &encode(&message), self.server)
+    }
+}
+
+impl Session for NetClient {
+    fn remote(&self) -> String {
+        self.server.to_string()
+    }
+
+    fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    fn status(&self, now: Instant) -> ClientStatus {
+        if let Some(reason) = &self.rejected {
+            return ClientStatus::Rejected(reason.clone());
+        }
+        if now.duration_since(self.last_heard) >= TIMEOUT {
+            return ClientStatus::TimedOut;
+        }
+        let (Some(id), Some(snapshot)) = (self.id, &self.snapshot) else {
+            return ClientStatus::Connecting;
+        };
+
+        match snapshot.snakes.iter().position(|snake| snake.client == Some(id)) {
+            Some(player) if !snapshot.over => ClientStatus::Playing(player),
+            _ => ClientStatus::Waiting,
+        }
+    }
+
+    fn heading(&self) -> Option<Direction> {
+        let id = self.id?;
+        let snake = self.snapshot.as_ref()?.snakes.iter().find(|snake| snake.client == Some(id))?;
+        Some(self.pending.last().map_or(snake.direction, |input| input.direction))
+    }
+
+    // The turn goes to the next free tick and is sent right away
+    fn steer(&mut self, direction: Direction, now: Instant) -> io::Result<bool> {
+        let Some(heading) = self.heading() else {
+            return Ok(false);
+        };
+        if self.pending.len() >= MAX_PENDING_INPUTS || direction == heading || direction == heading.opposite() {
+            return Ok(false);
+        }
+
+        let tick = self.snapshot.as_ref().map_or(0, |snapshot| snapshot.tick) + 1;
+        self.last_tick = self.last_tick.max(tick - 1) + 1;
+        self.pending.push(TickInput { tick: self.last_tick, direction });
+        self.send(now)?;
+        Ok(true)
+    }
+
+    // Unanswered joins and unacknowledged inputs are sent again
+    fn update(&mut self, now: Instant) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_PACKET];
+        while let Some((len, _)) = receive_from(&self.socket, &mut buffer, Some(self.server))? {
+            if let Some(message) = decode(&buffer[..len]) {
+                self.last_heard = now;
+                self.handle(message);
+            }
+        }
+
+        if self.last_sent.is_none_or(|sent| now.duration_since(sent) >= RESEND_INTERVAL) {
+            self.send(now)?;
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        self.socket.send_to(&encode(&ClientMessage::Leave), self.server)?;
+        Ok(())
+    }
+}
+
+// Non-blocking socket on any local port that can reach an address
+pub(crate) fn bind_any(remote: SocketAddr) -> io::Result<UdpSocket> {
+    let local: SocketAddr = if remote.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+// Next packet and its sender, only from one address when one is given. None once
+// there are no more.
+pub(crate) fn receive_from(socket: &UdpSocket, buffer: &mut [u8], from: Option<SocketAddr>) -> io::Result<Option<(usize, SocketAddr)>> {
+    loop {
+        match socket.recv_from(buffer) {
+            Ok((len, addr)) if from.is_none_or(|from| from == addr) => return Ok(Some((len, addr))),
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            // A peer that went away makes some platforms report an error for the next receive
+            Err(err) if err.kind() == io::ErrorKind::ConnectionReset => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// Keep updating until the condition holds, packets over localhost arrive in no time
+#[cfg(test)]
+pub(crate) fn pump_until<T>(state: &mut T, mut update: impl FnMut(&mut T, Instant), mut done: impl FnMut(&T) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while !done(state) {
+        assert!(Instant::now() < deadline, "timed out waiting");
+        update(state, Instant::now());
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// Log the first failed send to an address
+pub(crate) fn report_send_error(reported: &mut bool, addr: SocketAddr, err: &io::Error) {
+    if !*reported {
+        eprintln!("could not send to {addr}: {err}");
+        *reported = true;
+    }
+}
+
+// Send a packet, a full send buffer loses it like the network would
+pub(crate) fn send_lossy(socket: &UdpSocket, packet: &[u8], to: SocketAddr) -> io::Result<()> {
+    match socket.send_to(packet, to) {
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(min_players: usize) -> NetServer {
+        let config = ServerConfig {
+            grid: GridConfig::default(),
+            rules: Rules::default(),
+            min_players,
+            seed: 7,
+        };
+        NetServer::bind("127.0.0.1:0", config).unwrap()
+    }
+
+    fn client(server: &NetServer, name: &str) -> NetClient {
+        NetClient::connect(server.local_addr().unwrap(), name).unwrap()
+    }
+
+    fn pump(server: &mut NetServer, clients: &mut [&mut NetClient], mut done: impl FnMut(&NetServer, &[&mut NetClient]) -> bool) {
+        let update = |(server, clients): &mut (&mut NetServer, &mut [&mut NetClient]), now| {
+            server.update(now).unwrap();
+            for client in clients.iter_mut() {
+                client.update(now).unwrap();
+            }
+        };
+        pump_until(&mut (server, clients), update, |(server, clients)| done(server, clients));
+    }
+
+    #[test]
+    fn join_steer_and_leave() {
+        let mut server = server(1);
+        let mut ann = client(&server, "Ann");
+
+        pump(&mut server, &mut [&mut ann], |_, clients| {
+            clients[0].status(Instant::now()) == ClientStatus::Playing(0)
+        });
+        let snapshot = ann.snapshot().unwrap();
+        assert_eq!(snapshot.lobby, ["Ann"]);
+        assert_eq!(snapshot.snakes[0].name, "Ann");
+        assert_eq!(ann.heading(), Some(Direction::Up));
+
+        // The turn reaches the game and the server acknowledges it
+        assert!(ann.steer(Direction::Right, Instant::now()).unwrap());
+        assert!(!ann.steer(Direction::Left, Instant::now()).unwrap());
+        pump(&mut server, &mut [&mut ann], |server, clients| {
+            server.sim().unwrap().snake(0).direction() == Direction::Right && clients[0].snapshot().unwrap().ack > 0
+        });
+        assert_eq!(ann.snapshot().unwrap().snakes[0].direction, Direction::Right);
+
+        // Leaving frees the snake right away
+        ann.leave().unwrap();
+        pump(&mut server, &mut [], |server, _| server.clients() == 0);
+        assert!(!server.sim().unwrap().snake(0).is_alive());
+    }
+
+    #[test]
+    fn quiet_clients_time_out() {
+        let mut server = server(2);
+        let mut ann = client(&server, "Ann");
+        let mut bob = client(&server, "Bob");
+
+        pump(&mut server, &mut [&mut ann, &mut bob], |server, clients| {
+            server.round() == 1 && clients.iter().all(|client| matches!(client.status(Instant::now()), ClientStatus::Playing(_)))
+        });
+
+        // Bob keeps talking, Ann goes quiet
+        let later = Instant::now() + TIMEOUT;
+        bob.update(later).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        server.update(later).unwrap();
+        assert_eq!(server.clients(), 1);
+        assert_eq!(server.sim().unwrap().snakes().iter().filter(|snake| snake.is_alive()).count(), 1);
+        assert_eq!(ann.status(later), ClientStatus::TimedOut);
+    }
+
+    #[test]
+    fn names_are_cleaned_up() {
+        assert_eq!(clean_name("  Ann\n\t "), Some("Ann".to_string()));
+        assert_eq!(clean_name(&"x".repeat(10_000)), Some("x".repeat(MAX_NAME_LEN)));
+        assert_eq!(clean_name("\u{7}\u{1b}"), None);
+
+        let mut server = server(1);
+        let mut long = client(&server, &"\u{1F40D}".repeat(5_000));
+        let mut blank = client(&server, "\n");
+        pump(&mut server, &mut [&mut long, &mut blank], |_, clients| {
+            clients.iter().all(|client| client.snapshot().is_some_and(|snapshot| snapshot.lobby.len() == 2))
+        });
+        assert_eq!(long.snapshot().unwrap().lobby, ["\u{1F40D}".repeat(MAX_NAME_LEN), "Player 2".to_string()]);
+    }
+
+    #[test]
+    fn clients_of_another_version_are_rejected() {
+        let mut server = server(1);
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let join = ClientMessage::Join { version: PROTOCOL_VERSION + 1, name: "Old".to_string() };
+        socket.send_to(&encode(&join), server.local_addr().unwrap()).unwrap();
+
+        socket.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        server.update(Instant::now()).unwrap();
+        let mut buffer = vec![0; MAX_PACKET];
+        let (len, _) = socket.recv_from(&mut buffer).unwrap();
+        assert!(matches!(decode(&buffer[..len]), Some(ServerMessage::Rejected { .. })));
+        assert_eq!(server.clients(), 0);
+    }
+
+    #[test]
+    fn unreachable_clients_dont_stop_the_server() {
+        let mut server = server(1);
+        let join = ClientMessage::Join { version: PROTOCOL_VERSION, name: "Nobody".to_string() };
+
+        // Sending to the broadcast address fails without SO_BROADCAST
+        server.handle(join, "255.255.255.255:9".parse().unwrap(), Instant::now());
+        server.update(Instant::now()).unwrap();
+        assert_eq!(server.clients(), 1);
+        assert_eq!(server.round(), 1);
+    }
+
+    #[test]
+    fn servers_hold_few_inputs_and_only_for_snakes_of_the_round() {
+        let mut server = server(1);
+        let ann: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let bob: SocketAddr = "127.0.0.1:10".parse().unwrap();
+        let join = |name: &str| ClientMessage::Join { version: PROTOCOL_VERSION, name: name.to_string() };
+        let input = |ticks: std::ops::RangeInclusive<u32>| ClientMessage::Input {
+            round: 1,
+            inputs: ticks.map(|tick| TickInput { tick, direction: Direction::Right }).collect(),
+        };
+
+        server.handle(join("Ann"), ann, Instant::now());
+        server.update(Instant::now()).unwrap();
+        server.handle(join("Bob"), bob, Instant::now());
+        assert_eq!((server.round(), server.sim().unwrap().ticks()), (1, 0));
+
+        // Ticks far ahead are neither kept nor acknowledged
+        server.handle(input(1_000..=1_010), ann, Instant::now());
+        assert!(server.clients[0].inputs.is_empty());
+        assert_eq!(server.clients[0].ack, 0);
+
+        // A flood of ticks is cut off at the window
+        server.handle(input(1..=10_000), ann, Instant::now());
+        assert_eq!(server.clients[0].inputs.len(), MAX_HELD_INPUTS);
+        assert_eq!(server.clients[0].ack, MAX_HELD_INPUTS as u32);
+
+        // Bob joined after the round started and has no snake to steer
+        server.handle(input(1..=2), bob, Instant::now());
+        assert!(server.clients[1].inputs.is_empty());
+        assert_eq!(server.clients[1].ack, 0);
+    }
+
+    #[test]
+    fn boards_too_large_for_a_packet_are_refused() {
+        let mut config = ServerConfig {
+            grid: GridConfig { width: 200, height: 200, ..GridConfig::default() },
+            rules: Rules::default(),
+            min_players: 2,
+            seed: 0,
+        };
+        let err = NetServer::bind("127.0.0.1:0", config.clone()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        config.grid = GridConfig { width: 60, height: 60, ..GridConfig::default() };
+        config.validate().unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Direction,
+    sim::{GridConfig, Layout, Rules, SnakeSim},
+    storage::{StorageError, data_dir, parse_ron, save_ron},
+};
+
+// Bumped whenever the replay format or the simulation rules change
+pub const REPLAY_VERSION: u32 = 7;
+
+// Ticks between the snapshots kept for seeking
+const CHECKPOINT_TICKS: u32 = 100;
+
+// A direction change and the tick it was applied on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayInput {
+    pub tick: u32,
+    pub player: usize,
+    pub direction: Direction,
+}
+
+// Everything needed to replay a game tick by tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub layout: Layout, // of the level, empty on an open board
+    pub players: usize,
+    pub rivals: usize,
+    pub score: u32, // of the first player
+    pub ticks: u32,
+    pub inputs: Vec<ReplayInput>,
+}
+
+// Read first so older replays fail with a clear version error
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+impl Replay {
+    // Create a replay from a finished game
+    pub fn record(sim: &SnakeSim, inputs: Vec<ReplayInput>) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed: sim.seed(),
+            grid: *sim.grid(),
+            rules: *sim.rules(),
+            layout: sim.layout().clone(),
+            players: sim.players(),
+            rivals: sim.rivals(),
+            score: sim.snake(0).score(),
+            ticks: sim.ticks(),
+            inputs,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let text = fs::read_to_string(path)?;
+
+        let header: ReplayHeader = parse_ron(&text)?;
+        if header.version != REPLAY_VERSION {
+            return Err(StorageError::Version { found: header.version, expected: REPLAY_VERSION });
+        }
+
+        parse_ron(&text)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StorageError> {
+        save_ron(self, path)
+    }
+
+    // Folder the finished games are saved to, next to the leaderboard
+    pub fn default_dir() -> PathBuf {
+        data_dir().join("replays")
+    }
+}
+
+// A replay prepared for playback, with the inputs indexed by tick and
+// snapshots of the simulation so seeking doesn't start over from tick 0
+pub struct Playback {
+    replay: Replay,
+    directions: Vec<BTreeMap<u32, Direction>>, // per player
+    checkpoints: Vec<SnakeSim>,                // every CHECKPOINT_TICKS ticks
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let mut directions = vec![BTreeMap::new(); replay.players];
+        for input in &replay.inputs {
+            if let Some(player) = directions.get_mut(input.player) {
+                player.insert(input.tick, input.direction);
+            }
+        }
+
+        let mut playback = Self { replay, directions, checkpoints: Vec::new() };
+
+        // Play the whole game once and keep a snapshot every few ticks
+        let mut sim = playback.start();
+        loop {
+            if sim.ticks().is_multiple_of(CHECKPOINT_TICKS) {
+                playback.checkpoints.push(sim.clone());
+            }
+            if sim.ticks() >= playback.replay.ticks || sim.is_over() {
+                break;
+            }
+            playback.step(&mut sim);
+        }
+
+        playback
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    // Direction a recorded player steered into at a tick
+    pub fn direction_at(&self, player: usize, tick: u32) -> Option<Direction> {
+        self.directions
+            .get(player)?
+            .range(..=tick)
+            .next_back()
+            .map(|(_, &direction)| direction)
+    }
+
+    // Re-simulate the game up to a tick from the closest checkpoint before it
+    pub fn simulate_to(&self, tick: u32) -> SnakeSim {
+        let tick = tick.min(self.replay.ticks);
+        let checkpoint = (tick / CHECKPOINT_TICKS) as usize;
+        let mut sim = match self.checkpoints.get(checkpoint).or(self.checkpoints.last()) {
+            Some(sim) => sim.clone(),
+            None => self.start(),
+        };
+
+        while sim.ticks() < tick && !sim.is_over() {
+            self.step(&mut sim);
+        }
+
+        sim
+    }
+
+    fn start(&self) -> SnakeSim {
+        SnakeSim::with_players(self.replay.grid, self.replay.rules, self.replay.seed, self.replay.players)
+            .with_rivals(self.replay.rivals)
+            .with_layout(self.replay.layout.clone())
+    }
+
+    fn step(&self, sim: &mut SnakeSim) {
+        let directions: Vec<Direction> = sim
+            .snakes()
+            .iter()
+            .enumerate()
+            .map(|(player, snake)| self.direction_at(player, sim.ticks()).unwrap_or(snake.direction()))
+            .collect();
+        sim.tick(&directions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::BoardMode;
+
+    // Play a wrap-around game that drifts right every few ticks, recording the turns like the game does
+    fn recorded_game(seed: u64) -> (SnakeSim, Vec<ReplayInput>, Vec<u64>) {
+        let rules = Rules { board: BoardMode::Wrap, ..Rules::default() };
+        let mut sim = SnakeSim::new(GridConfig::default(), rules, seed);
+        let mut inputs = Vec::new();
+        let mut hashes = vec![sim.state_hash()];
+
+        while sim.ticks() < 350 && !sim.is_over() {
+            let direction = if sim.ticks() % 7 == 6 { Direction::Right } else { Direction::Up };
+            if direction != sim.snake(0).direction() {
+                inputs.push(ReplayInput { tick: sim.ticks(), player: 0, direction });
+            }
+            sim.tick(&[direction]);
+            hashes.push(sim.state_hash());
+        }
+
+        (sim, inputs, hashes)
+    }
+
+    #[test]
+    fn saved_replay_plays_back_to_the_same_state() {
+        let (sim, inputs, hashes) = recorded_game(42);
+        assert!(sim.ticks() > 2 * CHECKPOINT_TICKS, "game ended after {} ticks", sim.ticks());
+
+        let path = std::env::temp_dir().join(format!("snake_replay_{}.ron", std::process::id()));
+        Replay::record(&sim, inputs).save(&path).unwrap();
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let playback = Playback::new(replay);
+        assert_eq!(playback.simulate_to(sim.ticks()).state_hash(), sim.state_hash());
+
+        // Seeking backwards and across checkpoints lands on the recorded states
+        for tick in [sim.ticks() - 1, 250, 200, 199, 101, 100, 37, 0] {
+            assert_eq!(playback.simulate_to(tick).state_hash(), hashes[tick as usize], "tick {tick}");
+        }
+    }
+
+    #[test]
+    fn directions_come_from_the_latest_input_of_the_player() {
+        let sim = SnakeSim::with_players(GridConfig::default(), Rules::default(), 1, 2);
+        let inputs = vec![
+            ReplayInput { tick: 3, player: 0, direction: Direction::Left },
+            ReplayInput { tick: 5, player: 1, direction: Direction::Down },
+            ReplayInput { tick: 9, player: 0, direction: Direction::Up },
+        ];
+        let playback = Playback::new(Replay::record(&sim, inputs));
+
+        assert_eq!(playback.direction_at(0, 2), None);
+        assert_eq!(playback.direction_at(0, 3), Some(Direction::Left));
+        assert_eq!(playback.direction_at(0, 8), Some(Direction::Left));
+        assert_eq!(playback.direction_at(0, 9), Some(Direction::Up));
+        assert_eq!(playback.direction_at(1, 4), None);
+        assert_eq!(playback.direction_at(1, 100), Some(Direction::Down));
+        assert_eq!(playback.direction_at(2, 100), None);
+    }
+}
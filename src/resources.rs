@@ -1,5 +1,17 @@
 use bevy::prelude::*;
-use crate::components::Position;
+
+use std::path::PathBuf;
+
+use crate::{
+    ai::AiSkill,
+    leaderboard::Leaderboard,
+    level::Level,
+    net::Session,
+    replay::{Playback, ReplayInput},
+    settings::{Action, Settings},
+    sim::{Rules, SnakeSim, TickReport},
+    spectator::SpectatorServer,
+};
 
 // Timer to control the tick speed of the snake movement
 #[derive(Resource)]
@@ -11,30 +23,152 @@ impl Default for MovementTimer {
     }
 }
 
-// Timer to spawn the foods
+impl MovementTimer {
+    pub fn from_millis(millis: u32) -> Self {
+        Self(Timer::new(std::time::Duration::from_millis(millis as u64), TimerMode::Repeating))
+    }
+}
+
+// Rule settings for new games
+#[derive(Resource, Default)]
+pub struct GameRules(pub Rules);
+
+// Seed of the current game, every random decision of a game derives from it
+#[derive(Resource)]
+pub struct GameSeed {
+    pub fixed: Option<u64>, // seed from the command line, a fresh one per game otherwise
+    pub current: u64,
+}
+
+impl GameSeed {
+    pub fn new(fixed: Option<u64>) -> Self {
+        Self {
+            fixed,
+            current: fixed.unwrap_or_else(rand::random),
+        }
+    }
+
+    // Pick the seed for a new game
+    pub fn next_game(&mut self) -> u64 {
+        self.current = self.fixed.unwrap_or_else(rand::random);
+        self.current
+    }
+}
+
+// Segment entities of every snake, head first
+#[derive(Resource, Default)]
+pub struct SnakeSegments(pub Vec<Vec<Entity>>);
+
+// Number of snakes in new games
 #[derive(Resource)]
-pub struct FoodSpawnTimer(pub Timer);
+pub struct PlayerCount(pub usize);
 
-impl Default for FoodSpawnTimer {
+impl Default for PlayerCount {
     fn default() -> Self {
-        Self(Timer::from_seconds(1.5, TimerMode::Repeating))
+        Self(1)
     }
 }
 
-// Snake segments
+// Computer rivals in new games
 #[derive(Resource, Default)]
-pub struct SnakeSegments(pub Vec<Entity>);
+pub struct Rivals {
+    pub count: usize,
+    pub skill: AiSkill,
+}
 
+// Levels found in the assets and the one new games play, None for the open board
 #[derive(Resource, Default)]
-pub struct LastTailPosition(pub Option<Position>);
+pub struct Levels {
+    pub dir: PathBuf, // new levels of the editor are saved here
+    pub levels: Vec<Level>,
+    pub selected: Option<usize>,
+    pub playtest: Option<Level>, // tried out from the editor, played instead of the selected one
+}
 
-// Direction lock to block multiple direction changes in the same tick
+impl Levels {
+    pub fn current(&self) -> Option<&Level> {
+        self.playtest.as_ref().or(self.selected.and_then(|index| self.levels.get(index)))
+    }
+}
+
+// What a click on the board does in the level editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EditorTool {
+    #[default]
+    Wall,
+    Floor,
+    FoodZone,
+    Start, // clicking the head again turns it
+}
+
+impl EditorTool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Wall => "Wall",
+            Self::Floor => "Floor",
+            Self::FoodZone => "Food zone",
+            Self::Start => "Start",
+        }
+    }
+}
+
+// Level in the editor, kept while it is tried out
+#[derive(Resource)]
+pub struct LevelEditor {
+    pub level: Level,
+    pub tool: EditorTool,
+    pub unsaved: bool,
+    pub status: String, // result of the last save or check
+}
+
+impl LevelEditor {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            tool: EditorTool::default(),
+            unsaved: false,
+            status: String::new(),
+        }
+    }
+}
+
+// Agent that takes over the snake of the first player
+#[derive(Resource)]
+pub struct Autopilot {
+    pub enabled: bool,
+    pub agent: String, // name in the agent registry
+    pub engaged: bool, // steered at some point of the running game
+}
+
+impl Default for Autopilot {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            agent: "astar".to_string(),
+            engaged: false,
+        }
+    }
+}
+
+// Connection to a game server or a peer, only there while playing online
+#[derive(Resource)]
+pub struct Network(pub Box<dyn Session>);
+
+// WebSocket endpoint for spectators, only there when enabled on the command line
+#[derive(Resource)]
+pub struct Spectators(pub SpectatorServer);
+
+// Headless game state that the systems drive
+#[derive(Resource)]
+pub struct Sim(pub SnakeSim);
+
+// Countdown before a paused game continues
 #[derive(Resource, Default)]
-pub struct DirectionLocked(pub bool);
+pub struct ResumeCountdown(pub Option<Timer>);
 
-// Check collisions for a new tile
+// Result of a movement tick
 #[derive(Message)]
-pub struct NextHeadPosition(pub Position);
+pub struct TickOutcome(pub TickReport);
 
 // Snake eats food
 #[derive(Message)]
@@ -44,6 +178,14 @@ pub struct GrowthEvent;
 #[derive(Message)]
 pub struct GameOverEvent;
 
+// Start the running game over
+#[derive(Message)]
+pub struct RestartGame;
+
+// Jump to a tick of the replay
+#[derive(Message)]
+pub struct SeekReplay(pub u32);
+
 // Game States
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
@@ -51,12 +193,61 @@ pub enum GameState {
     Menu,
     Playing,
     GameOver,
+    Replay,
+    Paused,
+    Controls,
+    Online,
+    Editor,
+}
+
+// Score of every snake
+#[derive(Resource, Default)]
+pub struct Score(pub Vec<u32>);
+
+// Persistent leaderboard and the file it is stored in
+#[derive(Resource)]
+pub struct HighScores {
+    pub leaderboard: Leaderboard,
+    pub path: PathBuf,
 }
 
-// Score
+impl HighScores {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            leaderboard: Leaderboard::load_or_default(&path),
+            path,
+        }
+    }
+}
+
+// Player settings and the file they are stored in
+#[derive(Resource)]
+pub struct GameSettings {
+    pub settings: Settings,
+    pub path: PathBuf,
+}
+
+impl GameSettings {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            settings: Settings::load_or_default(&path),
+            path,
+        }
+    }
+}
+
+// Key binding slot on the controls screen that waits for a key press
+#[derive(Resource, Default)]
+pub struct PendingBinding(pub Option<(Action, usize)>);
+
+// Name stored with the scores of this session
+#[derive(Resource)]
+pub struct PlayerName(pub String);
+
+// Direction changes of the running game
 #[derive(Resource, Default)]
-pub struct Score(pub u32);
+pub struct ReplayRecorder(pub Vec<ReplayInput>);
 
-//HighScore
+// Replay of the last finished game or the one loaded from the command line
 #[derive(Resource, Default)]
-pub struct HighScore(pub u32);
\ No newline at end of file
+pub struct CurrentReplay(pub Option<Playback>);
@@ -0,0 +1,368 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use bevy::input::{ButtonInput, keyboard::KeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Direction,
+    sim::SpeedCurve,
+    storage::{StorageError, data_dir, load_or_backup, load_ron, save_ron},
+};
+
+// Number of keys that can be bound to one action
+pub const KEYS_PER_ACTION: usize = 2;
+
+// Something the player can do with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    TurnUp,
+    TurnDown,
+    TurnLeft,
+    TurnRight,
+    RotateLeft,
+    RotateRight,
+    Pause,
+    Restart,
+    Autopilot,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::TurnUp,
+        Action::TurnDown,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Pause,
+        Action::Restart,
+        Action::Autopilot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TurnUp => "Turn up",
+            Self::TurnDown => "Turn down",
+            Self::TurnLeft => "Turn left",
+            Self::TurnRight => "Turn right",
+            Self::RotateLeft => "Rotate left",
+            Self::RotateRight => "Rotate right",
+            Self::Pause => "Pause",
+            Self::Restart => "Restart",
+            Self::Autopilot => "Autopilot",
+        }
+    }
+
+    // Direction of an absolute steering action
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Self::TurnUp => Some(Direction::Up),
+            Self::TurnDown => Some(Direction::Down),
+            Self::TurnLeft => Some(Direction::Left),
+            Self::TurnRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    // Turn of a relative steering action from the current heading
+    pub fn rotate(self, heading: Direction) -> Option<Direction> {
+        match self {
+            Self::RotateLeft => Some(heading.turn_left()),
+            Self::RotateRight => Some(heading.turn_right()),
+            _ => None,
+        }
+    }
+}
+
+// How the steering keys are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SteeringMode {
+    // Arrow keys point the snake in a direction
+    #[default]
+    Absolute,
+    // Two keys rotate the snake relative to its heading
+    Relative,
+}
+
+impl SteeringMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Absolute => "Absolute",
+            Self::Relative => "Relative",
+        }
+    }
+
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Absolute,
+        }
+    }
+}
+
+// Keys that can be bound and the names they are saved under
+const KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::ArrowUp, "Up"),
+    (KeyCode::ArrowDown, "Down"),
+    (KeyCode::ArrowLeft, "Left"),
+    (KeyCode::ArrowRight, "Right"),
+    (KeyCode::KeyA, "A"),
+    (KeyCode::KeyB, "B"),
+    (KeyCode::KeyC, "C"),
+    (KeyCode::KeyD, "D"),
+    (KeyCode::KeyE, "E"),
+    (KeyCode::KeyF, "F"),
+    (KeyCode::KeyG, "G"),
+    (KeyCode::KeyH, "H"),
+    (KeyCode::KeyI, "I"),
+    (KeyCode::KeyJ, "J"),
+    (KeyCode::KeyK, "K"),
+    (KeyCode::KeyL, "L"),
+    (KeyCode::KeyM, "M"),
+    (KeyCode::KeyN, "N"),
+    (KeyCode::KeyO, "O"),
+    (KeyCode::KeyP, "P"),
+    (KeyCode::KeyQ, "Q"),
+    (KeyCode::KeyR, "R"),
+    (KeyCode::KeyS, "S"),
+    (KeyCode::KeyT, "T"),
+    (KeyCode::KeyU, "U"),
+    (KeyCode::KeyV, "V"),
+    (KeyCode::KeyW, "W"),
+    (KeyCode::KeyX, "X"),
+    (KeyCode::KeyY, "Y"),
+    (KeyCode::KeyZ, "Z"),
+    (KeyCode::Digit0, "0"),
+    (KeyCode::Digit1, "1"),
+    (KeyCode::Digit2, "2"),
+    (KeyCode::Digit3, "3"),
+    (KeyCode::Digit4, "4"),
+    (KeyCode::Digit5, "5"),
+    (KeyCode::Digit6, "6"),
+    (KeyCode::Digit7, "7"),
+    (KeyCode::Digit8, "8"),
+    (KeyCode::Digit9, "9"),
+    (KeyCode::Numpad0, "Numpad0"),
+    (KeyCode::Numpad1, "Numpad1"),
+    (KeyCode::Numpad2, "Numpad2"),
+    (KeyCode::Numpad3, "Numpad3"),
+    (KeyCode::Numpad4, "Numpad4"),
+    (KeyCode::Numpad5, "Numpad5"),
+    (KeyCode::Numpad6, "Numpad6"),
+    (KeyCode::Numpad7, "Numpad7"),
+    (KeyCode::Numpad8, "Numpad8"),
+    (KeyCode::Numpad9, "Numpad9"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Escape, "Escape"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::ShiftLeft, "LeftShift"),
+    (KeyCode::ShiftRight, "RightShift"),
+    (KeyCode::ControlLeft, "LeftCtrl"),
+    (KeyCode::ControlRight, "RightCtrl"),
+    (KeyCode::AltLeft, "LeftAlt"),
+    (KeyCode::AltRight, "RightAlt"),
+    (KeyCode::Comma, "Comma"),
+    (KeyCode::Period, "Period"),
+    (KeyCode::Semicolon, "Semicolon"),
+    (KeyCode::Slash, "Slash"),
+    (KeyCode::Minus, "Minus"),
+    (KeyCode::Equal, "Equal"),
+    (KeyCode::F1, "F1"),
+    (KeyCode::F2, "F2"),
+    (KeyCode::F3, "F3"),
+    (KeyCode::F4, "F4"),
+    (KeyCode::F5, "F5"),
+    (KeyCode::F6, "F6"),
+    (KeyCode::F7, "F7"),
+    (KeyCode::F8, "F8"),
+    (KeyCode::F9, "F9"),
+    (KeyCode::F10, "F10"),
+    (KeyCode::F11, "F11"),
+    (KeyCode::F12, "F12"),
+];
+
+// A bindable key, stored by its name in the settings file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(pub KeyCode);
+
+impl Key {
+    // Only keys with a name can be bound
+    pub fn new(code: KeyCode) -> Option<Self> {
+        KEY_NAMES.iter().any(|&(key, _)| key == code).then_some(Self(code))
+    }
+
+    pub fn name(self) -> &'static str {
+        KEY_NAMES
+            .iter()
+            .find(|&&(key, _)| key == self.0)
+            .map_or("?", |&(_, name)| name)
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        KEY_NAMES
+            .iter()
+            .find(|&&(_, key_name)| key_name.eq_ignore_ascii_case(&name))
+            .map(|&(key, _)| Self(key))
+            .ok_or_else(|| format!("unknown key `{name}`"))
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key.name().to_string()
+    }
+}
+
+// Keys bound to every action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyMap {
+    pub bindings: BTreeMap<Action, [Option<Key>; KEYS_PER_ACTION]>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let defaults = [
+            // The first slot steers the first player in versus games
+            (Action::TurnUp, [KeyCode::KeyW, KeyCode::ArrowUp]),
+            (Action::TurnDown, [KeyCode::KeyS, KeyCode::ArrowDown]),
+            (Action::TurnLeft, [KeyCode::KeyA, KeyCode::ArrowLeft]),
+            (Action::TurnRight, [KeyCode::KeyD, KeyCode::ArrowRight]),
+            (Action::RotateLeft, [KeyCode::KeyQ, KeyCode::KeyJ]),
+            (Action::RotateRight, [KeyCode::KeyE, KeyCode::KeyL]),
+            (Action::Pause, [KeyCode::Escape, KeyCode::KeyP]),
+            (Action::Restart, [KeyCode::KeyR, KeyCode::F5]),
+            (Action::Autopilot, [KeyCode::KeyT, KeyCode::F2]),
+        ];
+
+        Self {
+            bindings: defaults
+                .into_iter()
+                .map(|(action, keys)| (action, keys.map(|key| Some(Key(key)))))
+                .collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    pub fn keys(&self, action: Action) -> [Option<Key>; KEYS_PER_ACTION] {
+        self.bindings.get(&action).copied().unwrap_or_default()
+    }
+
+    // Bind a key to one of the slots of an action
+    pub fn bind(&mut self, action: Action, slot: usize, key: Option<Key>) {
+        let keys = self.bindings.entry(action).or_default();
+        if let Some(binding) = keys.get_mut(slot) {
+            *binding = key;
+        }
+    }
+
+    // Actions a key is bound to
+    pub fn actions(&self, code: KeyCode) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, keys)| keys.contains(&Some(Key(code))))
+            .map(|(&action, _)| action)
+    }
+
+    // Actions a key is bound to together with the slot it is bound in
+    pub fn slot_actions(&self, code: KeyCode) -> impl Iterator<Item = (Action, usize)> + '_ {
+        self.bindings.iter().flat_map(move |(&action, keys)| {
+            keys.iter()
+                .enumerate()
+                .filter(move |(_, key)| **key == Some(Key(code)))
+                .map(move |(slot, _)| (action, slot))
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action).into_iter().flatten().any(|key| input.just_pressed(key.0))
+    }
+
+    // Whether a key triggers more than one action
+    pub fn is_conflict(&self, key: Key) -> bool {
+        self.actions(key.0).count() > 1
+    }
+}
+
+// Player settings that are kept between sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub key_map: KeyMap,
+    #[serde(default)]
+    pub steering: SteeringMode,
+    // Seed every game starts from, a fresh one each game when None
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // Speed-up curve of every game, the one of the difficulty when None
+    #[serde(default)]
+    pub speed_curve: Option<SpeedCurve>,
+}
+
+impl Settings {
+    // Location of the settings in the platform data directory
+    pub fn default_path() -> PathBuf {
+        data_dir().join("settings.ron")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let mut settings: Self = load_ron(path)?;
+
+        // Actions added after the file was saved get their default keys
+        for (action, keys) in KeyMap::default().bindings {
+            settings.key_map.bindings.entry(action).or_insert(keys);
+        }
+        Ok(settings)
+    }
+
+    // Load the settings, falling back to the defaults if the file is missing or corrupt
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        load_or_backup(path, "settings", |path| Self::load(path))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StorageError> {
+        save_ron(self, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn seed_and_speed_curve_survive_a_save() {
+        let path = std::env::temp_dir().join(format!("snake_settings_{}.ron", std::process::id()));
+        let settings = Settings {
+            seed: Some(42),
+            speed_curve: Some(SpeedCurve::Stepped { every: 3, millis_per_step: 20, min_millis: 60 }),
+            ..Settings::default()
+        };
+        settings.save(&path).unwrap();
+        let loaded = Settings::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), settings);
+    }
+
+    #[test]
+    fn settings_of_older_versions_still_load() {
+        let settings: Settings = ron::from_str("(steering: Relative)").unwrap();
+        assert_eq!(settings.seed, None);
+        assert_eq!(settings.speed_curve, None);
+    }
+}
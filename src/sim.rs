@@ -0,0 +1,1027 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+
+use bevy::prelude::Resource;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Direction, Position};
+
+// Most snakes a board has start poses for
+pub const MAX_PLAYERS: usize = 4;
+
+// Board size and start pose of the snake
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridConfig {
+    pub width: i32,
+    pub height: i32,
+    pub start: Position,
+    pub start_direction: Direction,
+    pub start_length: u32, // including the head
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            width: 15,
+            height: 15,
+            start: Position { x: 3, y: 3 },
+            start_direction: Direction::Up,
+            start_length: 2,
+        }
+    }
+}
+
+impl GridConfig {
+    // Check if a position lies on the grid
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height
+    }
+
+    // Bring a position that left the grid back in on the opposite edge
+    pub fn wrap(&self, pos: Position) -> Position {
+        Position {
+            x: pos.x.rem_euclid(self.width),
+            y: pos.y.rem_euclid(self.height),
+        }
+    }
+
+    // Start tile and heading of a player, the others mirror the first snake across the board
+    pub fn start_pose(&self, player: usize) -> (Position, Direction) {
+        let Position { x, y } = self.start;
+        let mirrored = Position { x: self.width - 1 - x, y: self.height - 1 - y };
+        let direction = self.start_direction;
+        let horizontal = matches!(direction, Direction::Left | Direction::Right);
+
+        match player % MAX_PLAYERS {
+            0 => (self.start, direction),
+            1 => (mirrored, direction.opposite()),
+            2 => (Position { x: mirrored.x, y }, if horizontal { direction.opposite() } else { direction }),
+            _ => (Position { x, y: mirrored.y }, if horizontal { direction } else { direction.opposite() }),
+        }
+    }
+
+    // Tiles of the starting snake of a player, head first
+    pub fn start_body(&self, player: usize) -> impl Iterator<Item = Position> + '_ {
+        let (start, direction) = self.start_pose(player);
+        let behind = direction.opposite();
+        std::iter::successors(Some(start), move |pos| Some(pos.step(behind)))
+            .take(self.start_length as usize)
+    }
+
+    // Make sure the starting snakes fit on the board without touching
+    pub fn validate(&self, players: usize) -> Result<(), String> {
+        if self.width < 1 || self.height < 1 {
+            return Err(format!("grid {}x{} is empty", self.width, self.height));
+        }
+        if self.start_length < 1 {
+            return Err("the snake needs a length of at least 1".to_string());
+        }
+        if !(1..=MAX_PLAYERS).contains(&players) {
+            return Err(format!("the number of snakes must be between 1 and {MAX_PLAYERS}"));
+        }
+        if !self.start_body(0).all(|pos| self.contains(pos)) {
+            return Err(format!(
+                "a snake of length {} at ({}, {}) heading {:?} does not fit on a {}x{} grid",
+                self.start_length, self.start.x, self.start.y, self.start_direction, self.width, self.height,
+            ));
+        }
+
+        let mut occupied = HashSet::new();
+        for player in 0..players {
+            if !self.start_body(player).all(|pos| occupied.insert(pos)) {
+                return Err(format!(
+                    "{players} snakes of length {} don't fit next to each other on a {}x{} grid",
+                    self.start_length, self.width, self.height,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// What happens at the edge of the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BoardMode {
+    #[default]
+    Walled, // leaving the board kills the snake
+    Wrap,   // leaving the board brings the snake in on the opposite edge
+}
+
+impl BoardMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Walled => "Classic",
+            Self::Wrap => "Wrap-around",
+        }
+    }
+}
+
+// How the tick interval shrinks as the snake eats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeedCurve {
+    Constant,
+    // Faster by a fixed amount per eaten food
+    Linear { millis_per_food: u32, min_millis: u32 },
+    // Faster by a fixed amount every few eaten foods
+    Stepped { every: u32, millis_per_step: u32, min_millis: u32 },
+    // The interval keeps a percentage of itself per eaten food
+    Exponential { percent_per_food: u32, min_millis: u32 },
+}
+
+impl SpeedCurve {
+    // Tick interval after eating a number of foods
+    pub fn tick_millis(self, base_millis: u32, foods: u32) -> u32 {
+        match self {
+            Self::Constant => base_millis,
+            Self::Linear { millis_per_food, min_millis } => base_millis
+                .saturating_sub(millis_per_food.saturating_mul(foods))
+                .max(min_millis),
+            Self::Stepped { every, millis_per_step, min_millis } => base_millis
+                .saturating_sub(millis_per_step.saturating_mul(foods / every.max(1)))
+                .max(min_millis),
+            Self::Exponential { percent_per_food, min_millis } => {
+                let factor = (percent_per_food as f64 / 100.0).powi(foods as i32);
+                ((base_millis as f64 * factor).round() as u32).max(min_millis)
+            }
+        }
+        .min(base_millis)
+    }
+
+    // Curve of the given name that speeds up to twice the base speed
+    pub fn with_name(name: &str, base_millis: u32) -> Result<Self, String> {
+        let min_millis = base_millis / 2;
+
+        match name.to_ascii_lowercase().as_str() {
+            "off" | "constant" => Ok(Self::Constant),
+            "linear" => Ok(Self::Linear { millis_per_food: 3, min_millis }),
+            "stepped" => Ok(Self::Stepped { every: 5, millis_per_step: 15, min_millis }),
+            "exponential" => Ok(Self::Exponential { percent_per_food: 97, min_millis }),
+            _ => Err(format!("unknown speed curve `{name}`")),
+        }
+    }
+
+    // Curve with some of its parameters changed, the ones it doesn't have are ignored
+    pub fn with_params(self, params: CurveParams) -> Result<Self, String> {
+        if params.every == Some(0) {
+            return Err("a stepped curve needs at least 1 food per step".to_string());
+        }
+        if params.percent.is_some_and(|percent| !(1..=100).contains(&percent)) {
+            return Err("the exponential rate must be between 1 and 100 percent".to_string());
+        }
+
+        Ok(match self {
+            Self::Constant => Self::Constant,
+            Self::Linear { millis_per_food, min_millis } => Self::Linear {
+                millis_per_food: params.step_millis.unwrap_or(millis_per_food),
+                min_millis: params.min_millis.unwrap_or(min_millis),
+            },
+            Self::Stepped { every, millis_per_step, min_millis } => Self::Stepped {
+                every: params.every.unwrap_or(every),
+                millis_per_step: params.step_millis.unwrap_or(millis_per_step),
+                min_millis: params.min_millis.unwrap_or(min_millis),
+            },
+            Self::Exponential { percent_per_food, min_millis } => Self::Exponential {
+                percent_per_food: params.percent.unwrap_or(percent_per_food),
+                min_millis: params.min_millis.unwrap_or(min_millis),
+            },
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Constant => "Constant",
+            Self::Linear { .. } => "Linear",
+            Self::Stepped { .. } => "Stepped",
+            Self::Exponential { .. } => "Exponential",
+        }
+    }
+}
+
+// Parameters of a speed-up curve to change, None keeps the value of the curve
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CurveParams {
+    pub every: Option<u32>,       // foods per step of a stepped curve
+    pub step_millis: Option<u32>, // faster per food of a linear curve or per step of a stepped one
+    pub percent: Option<u32>,     // of the interval an exponential curve keeps per food
+    pub min_millis: Option<u32>,  // fastest tick interval
+}
+
+// Presets for the speed, food and scoring of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Insane,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [Self::Easy, Self::Normal, Self::Hard, Self::Insane];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+            Self::Insane => "Insane",
+        }
+    }
+
+    // The next harder preset, wrapping around to the easiest
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&d| d == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn rules(self) -> Rules {
+        let (tick_millis, food_millis, max_food, board, score_multiplier) = match self {
+            Self::Easy => (250, 1000, 8, BoardMode::Wrap, 1),
+            Self::Normal => (200, 1500, 10, BoardMode::Walled, 2),
+            Self::Hard => (140, 1800, 4, BoardMode::Walled, 3),
+            Self::Insane => (90, 2200, 2, BoardMode::Walled, 4),
+        };
+        let speed_curve = match self {
+            Self::Easy => SpeedCurve::Linear { millis_per_food: 2, min_millis: 150 },
+            Self::Normal => SpeedCurve::Stepped { every: 5, millis_per_step: 10, min_millis: 100 },
+            Self::Hard => SpeedCurve::Exponential { percent_per_food: 98, min_millis: 70 },
+            Self::Insane => SpeedCurve::Exponential { percent_per_food: 97, min_millis: 50 },
+        };
+
+        Rules {
+            difficulty: self,
+            tick_millis,
+            food_millis,
+            max_food,
+            board,
+            score_multiplier,
+            speed_curve,
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown difficulty `{s}`"))
+    }
+}
+
+// Rules that together with the grid and the seed define a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    pub difficulty: Difficulty, // preset the rules started from
+    pub tick_millis: u32,       // time between two movement ticks at the start
+    pub food_millis: u32,       // time between two food spawns
+    pub max_food: u32,          // no food spawns while this many are on the board
+    pub board: BoardMode,
+    pub score_multiplier: u32,  // points per eaten food
+    pub speed_curve: SpeedCurve,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Difficulty::default().rules()
+    }
+}
+
+impl Rules {
+    // Speed, food and scoring of a preset, the board and the speed-up curve picked elsewhere stay
+    pub fn with_difficulty(self, difficulty: Difficulty) -> Self {
+        Self {
+            board: self.board,
+            speed_curve: self.speed_curve,
+            ..difficulty.rules()
+        }
+    }
+}
+
+// What ends a level besides the snakes dying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Goal {
+    #[default]
+    Endless,
+    Score(u32),   // a snake reaches this score
+    Length(u32),  // a snake grows this long
+    Survive(u32), // a snake stays alive for this many seconds
+}
+
+// Walls inside the board, where food may spawn and the goal of a level
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    pub walls: HashSet<Position>,
+    pub food_zone: HashSet<Position>, // empty when food spawns on any free tile
+    pub goal: Goal,
+}
+
+// Reason a snake died
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Border, // ran into the edge of the board or a wall of the level
+    Tail,  // ran into its own body
+    Snake, // ran into the body of another snake
+    HeadOn, // moved onto the same tile as another snake
+    Forfeit, // its player left the game
+}
+
+impl DeathCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Border => "border",
+            Self::Tail => "tail",
+            Self::Snake => "snake",
+            Self::HeadOn => "head-on",
+            Self::Forfeit => "forfeit",
+        }
+    }
+}
+
+// What happened to a snake during a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Moved,
+    Ate(Position),
+    Died(DeathCause),
+}
+
+// Food on the board, the variant picks its sprite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FoodItem {
+    pub pos: Position,
+    pub variant: u32,
+}
+
+// Everything that happened during a single tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickReport {
+    pub outcomes: Vec<StepOutcome>, // one per snake
+    pub spawned_food: Option<FoodItem>,
+}
+
+// A single snake on the board
+#[derive(Debug, Clone)]
+pub struct Snake {
+    body: VecDeque<Position>, // head first
+    direction: Direction,
+    score: u32,
+    death: Option<DeathCause>,
+}
+
+impl Snake {
+    fn new(grid: &GridConfig, player: usize) -> Self {
+        Self {
+            body: grid.start_body(player).collect(),
+            direction: grid.start_pose(player).1,
+            score: 0,
+            death: None,
+        }
+    }
+
+    pub fn head(&self) -> Position {
+        self.body[0]
+    }
+
+    pub fn body(&self) -> impl Iterator<Item = Position> + '_ {
+        self.body.iter().copied()
+    }
+
+    pub fn length(&self) -> usize {
+        self.body.len()
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn death(&self) -> Option<DeathCause> {
+        self.death
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.death.is_none()
+    }
+}
+
+// Snake game rules without any Bevy dependency
+#[derive(Clone)]
+pub struct SnakeSim {
+    grid: GridConfig,
+    rules: Rules,
+    seed: u64,
+    rng: StdRng,
+    snakes: Vec<Snake>,
+    food: Vec<FoodItem>,
+    food_clock: u32, // milliseconds since the last food spawn
+    elapsed_millis: u64,
+    ticks: u32,
+    rivals: usize, // computer snakes at the end of the snake list
+    layout: Arc<Layout>, // shared by every clone, it never changes during a game
+}
+
+impl SnakeSim {
+    // Create a new single player game with the snake in its start pose
+    pub fn new(grid: GridConfig, rules: Rules, seed: u64) -> Self {
+        Self::with_players(grid, rules, seed, 1)
+    }
+
+    // Create a new game with a snake for every player
+    pub fn with_players(grid: GridConfig, rules: Rules, seed: u64, players: usize) -> Self {
+        Self {
+            grid,
+            rules,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            snakes: (0..players.max(1)).map(|player| Snake::new(&grid, player)).collect(),
+            food: Vec::new(),
+            food_clock: 0,
+            elapsed_millis: 0,
+            ticks: 0,
+            rivals: 0,
+            layout: Arc::default(),
+        }
+    }
+
+    // Mark the last snakes as computer rivals, the game ends once every other snake is out
+    pub fn with_rivals(mut self, rivals: usize) -> Self {
+        self.rivals = rivals.min(self.snakes.len() - 1);
+        self
+    }
+
+    // Play on the walls, food zone and goal of a level
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = Arc::new(layout);
+        self
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn is_wall(&self, pos: Position) -> bool {
+        self.layout.walls.contains(&pos)
+    }
+
+    pub fn grid(&self) -> &GridConfig {
+        &self.grid
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn width(&self) -> i32 {
+        self.grid.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.grid.height
+    }
+
+    pub fn players(&self) -> usize {
+        self.snakes.len()
+    }
+
+    pub fn rivals(&self) -> usize {
+        self.rivals
+    }
+
+    // Snakes steered by people, they come before the rivals
+    pub fn humans(&self) -> usize {
+        self.snakes.len() - self.rivals
+    }
+
+    pub fn is_rival(&self, player: usize) -> bool {
+        player >= self.humans()
+    }
+
+    pub fn snakes(&self) -> &[Snake] {
+        &self.snakes
+    }
+
+    pub fn snake(&self, player: usize) -> &Snake {
+        &self.snakes[player]
+    }
+
+    // Most foods any snake has eaten
+    pub fn foods_eaten(&self) -> u32 {
+        self.snakes
+            .iter()
+            .map(|snake| (snake.length() as u32).saturating_sub(self.grid.start_length))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Current time between two movement ticks
+    pub fn tick_millis(&self) -> u32 {
+        self.rules.speed_curve.tick_millis(self.rules.tick_millis, self.foods_eaten())
+    }
+
+    pub fn food(&self) -> &[FoodItem] {
+        &self.food
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    // Game time played so far
+    pub fn elapsed_millis(&self) -> u64 {
+        self.elapsed_millis
+    }
+
+    // A single snake plays until it dies, several until at most one is left
+    // or until every human is out. A level also ends once a human reaches its goal.
+    pub fn is_over(&self) -> bool {
+        let alive = self.snakes.iter().filter(|snake| snake.is_alive()).count();
+        let humans_alive = self.snakes[..self.humans()].iter().any(Snake::is_alive);
+        let out = if self.snakes.len() == 1 { alive == 0 } else { alive <= 1 || !humans_alive };
+        out || self.goal_reached()
+    }
+
+    // Whether a human snake reached the goal of the level
+    pub fn goal_reached(&self) -> bool {
+        self.snakes[..self.humans()].iter().any(|snake| self.reached_goal(snake))
+    }
+
+    fn reached_goal(&self, snake: &Snake) -> bool {
+        match self.layout.goal {
+            Goal::Endless => false,
+            Goal::Score(score) => snake.score >= score,
+            Goal::Length(length) => snake.length() >= length as usize,
+            Goal::Survive(seconds) => snake.is_alive() && self.elapsed_millis >= u64::from(seconds) * 1000,
+        }
+    }
+
+    // Fingerprint of the game state, equal on every machine that played the same inputs
+    pub fn state_hash(&self) -> u64 {
+        // FNV-1a, stable across platforms and Rust versions
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash = (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        write(u64::from(self.ticks));
+        write(self.elapsed_millis);
+        write(u64::from(self.food_clock));
+        // Covers the food that spawns next
+        write(self.rng.clone().r#gen());
+        for snake in &self.snakes {
+            write(snake.body.len() as u64);
+            for pos in &snake.body {
+                write(u64::from(pos.x as u32) << 32 | u64::from(pos.y as u32));
+            }
+            write(snake.direction as u64);
+            write(u64::from(snake.score));
+            write(snake.death.map_or(0, |cause| cause as u64 + 1));
+        }
+        for food in &self.food {
+            write(u64::from(food.pos.x as u32) << 32 | u64::from(food.pos.y as u32));
+            write(u64::from(food.variant));
+        }
+
+        hash
+    }
+
+    // Take a snake out of the game, its body stays on the board
+    pub fn forfeit(&mut self, player: usize) {
+        let snake = &mut self.snakes[player];
+        if snake.death.is_none() {
+            snake.death = Some(DeathCause::Forfeit);
+        }
+    }
+
+    // Last snake standing of a finished game, None for a draw, a single player
+    // or when several rivals outlived the humans
+    pub fn winner(&self) -> Option<usize> {
+        if self.snakes.len() == 1 || !self.is_over() {
+            return None;
+        }
+        if self.goal_reached() {
+            let mut reached = (0..self.humans()).filter(|&player| self.reached_goal(&self.snakes[player]));
+            return match (reached.next(), reached.next()) {
+                (Some(player), None) => Some(player),
+                _ => None,
+            };
+        }
+        let mut alive = self.snakes.iter().enumerate().filter(|(_, snake)| snake.is_alive());
+        match (alive.next(), alive.next()) {
+            (Some((player, _)), None) => Some(player),
+            _ => None,
+        }
+    }
+
+    // Check if a position lies on the grid
+    pub fn in_bounds(&self, pos: Position) -> bool {
+        self.grid.contains(pos)
+    }
+
+    // Tile a snake at a position moves onto in a direction, None when it leaves a walled
+    // board or runs into a wall of the level
+    pub fn neighbour(&self, pos: Position, direction: Direction) -> Option<Position> {
+        let mut next = pos.step(direction);
+        if !self.grid.contains(next) {
+            match self.rules.board {
+                BoardMode::Walled => return None,
+                BoardMode::Wrap => next = self.grid.wrap(next),
+            }
+        }
+        Some(next).filter(|&next| !self.is_wall(next))
+    }
+
+    // Advance the game by one tick with a direction per snake and spawn food when it is due
+    pub fn tick(&mut self, directions: &[Direction]) -> TickReport {
+        if self.is_over() {
+            return TickReport { outcomes: Vec::new(), spawned_food: None };
+        }
+
+        let tick_millis = self.tick_millis();
+        self.elapsed_millis += u64::from(tick_millis);
+        let outcomes = self.step(directions);
+
+        let mut spawned_food = None;
+        if !self.is_over() {
+            self.food_clock += tick_millis;
+            if self.food_clock >= self.rules.food_millis {
+                self.food_clock -= self.rules.food_millis;
+                if self.food.len() < self.rules.max_food as usize {
+                    spawned_food = self.spawn_food();
+                }
+            }
+        }
+
+        TickReport { outcomes, spawned_food }
+    }
+
+    // Move every living snake by one tile, snakes without a direction keep going straight
+    pub fn step(&mut self, directions: &[Direction]) -> Vec<StepOutcome> {
+        self.ticks += 1;
+
+        // Turn the snakes and find the tile each one moves onto
+        let mut targets: Vec<Result<Position, DeathCause>> = Vec::with_capacity(self.snakes.len());
+        for (player, snake) in self.snakes.iter_mut().enumerate() {
+            if let Some(cause) = snake.death {
+                targets.push(Err(cause));
+                continue;
+            }
+
+            // Prevent turning into the opposite direction
+            if let Some(&direction) = directions.get(player)
+                && direction != snake.direction.opposite()
+            {
+                snake.direction = direction;
+            }
+
+            let mut next = snake.head().step(snake.direction);
+
+            // Check for a border collision or wrap around the edge
+            if !self.grid.contains(next) {
+                match self.rules.board {
+                    BoardMode::Walled => {
+                        targets.push(Err(DeathCause::Border));
+                        continue;
+                    }
+                    BoardMode::Wrap => next = self.grid.wrap(next),
+                }
+            }
+
+            // Walls of the level kill like the edge of the board
+            if self.layout.walls.contains(&next) {
+                targets.push(Err(DeathCause::Border));
+                continue;
+            }
+            targets.push(Ok(next));
+        }
+
+        // Check the targets against the bodies and the other heads
+        let mut outcomes = Vec::with_capacity(self.snakes.len());
+        for (player, target) in targets.iter().enumerate() {
+            let next = match *target {
+                Ok(next) => next,
+                Err(cause) => {
+                    outcomes.push(StepOutcome::Died(cause));
+                    continue;
+                }
+            };
+
+            let body_hit = self.snakes.iter().enumerate().find(|&(other, snake)| {
+                // A snake can't run into its own head
+                let skip = usize::from(other == player);
+                snake.body.iter().skip(skip).any(|&pos| pos == next)
+            });
+            let head_on = targets
+                .iter()
+                .enumerate()
+                .any(|(other, &target)| other != player && target == Ok(next));
+
+            let outcome = match body_hit {
+                Some((other, _)) if other == player => StepOutcome::Died(DeathCause::Tail),
+                Some(_) => StepOutcome::Died(DeathCause::Snake),
+                None if head_on => StepOutcome::Died(DeathCause::HeadOn),
+                None => StepOutcome::Moved,
+            };
+            outcomes.push(outcome);
+        }
+
+        // Move the survivors, a snake keeps its tail when it grows
+        for (player, outcome) in outcomes.iter_mut().enumerate() {
+            let snake = &mut self.snakes[player];
+            if snake.death.is_some() {
+                continue;
+            }
+            if let StepOutcome::Died(cause) = *outcome {
+                snake.death = Some(cause);
+                continue;
+            }
+
+            let Ok(next) = targets[player] else {
+                continue;
+            };
+            snake.body.push_front(next);
+
+            if let Some(index) = self.food.iter().position(|food| food.pos == next) {
+                self.food.swap_remove(index);
+                snake.score += self.rules.score_multiplier;
+                *outcome = StepOutcome::Ate(next);
+            } else {
+                snake.body.pop_back();
+            }
+        }
+
+        outcomes
+    }
+
+    // All tiles that are neither snake, food nor wall, only the food zone of a level that has one
+    pub fn free_tiles(&self) -> Vec<Position> {
+        let occupied: HashSet<Position> = self
+            .snakes
+            .iter()
+            .flat_map(Snake::body)
+            .chain(self.food.iter().map(|food| food.pos))
+            .collect();
+        let mut free_tiles = Vec::new();
+
+        for x in 0..self.grid.width {
+            for y in 0..self.grid.height {
+                let pos = Position { x, y };
+                let in_zone = self.layout.food_zone.is_empty() || self.layout.food_zone.contains(&pos);
+                if in_zone && !occupied.contains(&pos) && !self.is_wall(pos) {
+                    free_tiles.push(pos);
+                }
+            }
+        }
+
+        free_tiles
+    }
+
+    // Place food on a random free tile, returns None if the board is full
+    pub fn spawn_food(&mut self) -> Option<FoodItem> {
+        let free_tiles = self.free_tiles();
+
+        if free_tiles.is_empty() {
+            return None;
+        }
+
+        let pos = free_tiles[self.rng.gen_range(0..free_tiles.len())];
+        let food = FoodItem {
+            pos,
+            variant: self.rng.r#gen(),
+        };
+        self.food.push(food);
+        Some(food)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hash after every tick of a game that circles the board with a fixed script of turns
+    fn scripted_hashes(seed: u64) -> Vec<u64> {
+        let mut sim = SnakeSim::new(GridConfig::default(), Rules::default(), seed);
+        let script = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+        let mut hashes = Vec::new();
+        for tick in 0..200 {
+            if sim.is_over() {
+                break;
+            }
+            sim.tick(&[script[tick / 3 % script.len()]]);
+            hashes.push(sim.state_hash());
+        }
+        hashes
+    }
+
+    #[test]
+    fn constant_curve_keeps_the_speed() {
+        assert_eq!(SpeedCurve::Constant.tick_millis(200, 0), 200);
+        assert_eq!(SpeedCurve::Constant.tick_millis(200, 1_000), 200);
+    }
+
+    #[test]
+    fn linear_curve_speeds_up_per_food_down_to_the_floor() {
+        let curve = SpeedCurve::Linear { millis_per_food: 3, min_millis: 150 };
+        assert_eq!(curve.tick_millis(200, 0), 200);
+        assert_eq!(curve.tick_millis(200, 10), 170);
+        assert_eq!(curve.tick_millis(200, 16), 152);
+        assert_eq!(curve.tick_millis(200, 17), 150);
+        assert_eq!(curve.tick_millis(200, u32::MAX), 150);
+    }
+
+    #[test]
+    fn stepped_curve_speeds_up_every_few_foods_down_to_the_floor() {
+        let curve = SpeedCurve::Stepped { every: 5, millis_per_step: 15, min_millis: 100 };
+        assert_eq!(curve.tick_millis(200, 4), 200);
+        assert_eq!(curve.tick_millis(200, 5), 185);
+        assert_eq!(curve.tick_millis(200, 14), 170);
+        assert_eq!(curve.tick_millis(200, 30), 110);
+        assert_eq!(curve.tick_millis(200, 35), 100);
+        assert_eq!(curve.tick_millis(200, u32::MAX), 100);
+
+        // A step of 0 foods counts like 1
+        let every_food = SpeedCurve::Stepped { every: 0, millis_per_step: 10, min_millis: 0 };
+        assert_eq!(every_food.tick_millis(200, 3), 170);
+    }
+
+    #[test]
+    fn exponential_curve_keeps_a_share_per_food_down_to_the_floor() {
+        let curve = SpeedCurve::Exponential { percent_per_food: 90, min_millis: 50 };
+        assert_eq!(curve.tick_millis(200, 0), 200);
+        assert_eq!(curve.tick_millis(200, 1), 180);
+        assert_eq!(curve.tick_millis(200, 2), 162);
+        assert_eq!(curve.tick_millis(200, 13), 51);
+        assert_eq!(curve.tick_millis(200, 14), 50);
+        assert_eq!(curve.tick_millis(200, 1_000), 50);
+    }
+
+    #[test]
+    fn floor_above_the_base_never_slows_the_snake_down() {
+        let curve = SpeedCurve::Linear { millis_per_food: 3, min_millis: 300 };
+        assert_eq!(curve.tick_millis(200, 10), 200);
+    }
+
+    #[test]
+    fn curve_params_change_only_the_given_values() {
+        let params = CurveParams { every: Some(2), step_millis: Some(20), percent: Some(80), min_millis: Some(60) };
+        assert_eq!(
+            SpeedCurve::with_name("stepped", 200).unwrap().with_params(params),
+            Ok(SpeedCurve::Stepped { every: 2, millis_per_step: 20, min_millis: 60 }),
+        );
+        assert_eq!(
+            SpeedCurve::with_name("linear", 200).unwrap().with_params(params),
+            Ok(SpeedCurve::Linear { millis_per_food: 20, min_millis: 60 }),
+        );
+        assert_eq!(
+            SpeedCurve::with_name("exponential", 200).unwrap().with_params(CurveParams { min_millis: Some(60), ..CurveParams::default() }),
+            Ok(SpeedCurve::Exponential { percent_per_food: 97, min_millis: 60 }),
+        );
+        assert_eq!(SpeedCurve::Constant.with_params(params), Ok(SpeedCurve::Constant));
+
+        assert!(SpeedCurve::Constant.with_params(CurveParams { every: Some(0), ..CurveParams::default() }).is_err());
+        assert!(SpeedCurve::Constant.with_params(CurveParams { percent: Some(101), ..CurveParams::default() }).is_err());
+    }
+
+    #[test]
+    fn changing_the_difficulty_keeps_the_board_and_the_speed_curve() {
+        let curve = SpeedCurve::Linear { millis_per_food: 7, min_millis: 40 };
+        let rules = Rules { board: BoardMode::Wrap, speed_curve: curve, ..Difficulty::Normal.rules() };
+
+        let hard = rules.with_difficulty(Difficulty::Hard);
+        assert_eq!(hard.board, BoardMode::Wrap);
+        assert_eq!(hard.speed_curve, curve);
+        let preset = Difficulty::Hard.rules();
+        assert_eq!(hard.difficulty, Difficulty::Hard);
+        assert_eq!(
+            (hard.tick_millis, hard.food_millis, hard.max_food, hard.score_multiplier),
+            (preset.tick_millis, preset.food_millis, preset.max_food, preset.score_multiplier),
+        );
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_the_same_state_hash() {
+        let hashes = scripted_hashes(42);
+        assert!(hashes.len() > 10);
+        assert_eq!(hashes, scripted_hashes(42));
+        assert_ne!(hashes, scripted_hashes(43));
+    }
+
+    // Snake of the default board at (3, 3) heading up, its tail on (3, 2)
+    fn walled() -> SnakeSim {
+        SnakeSim::new(GridConfig::default(), Rules::default(), 1)
+    }
+
+    fn body(sim: &SnakeSim) -> Vec<Position> {
+        sim.snake(0).body().collect()
+    }
+
+    #[test]
+    fn eating_grows_the_snake_by_its_tail() {
+        let layout = Layout {
+            food_zone: HashSet::from([Position { x: 3, y: 5 }]),
+            ..Layout::default()
+        };
+        let mut sim = walled().with_layout(layout);
+        sim.spawn_food().unwrap();
+
+        assert_eq!(sim.tick(&[Direction::Up]).outcomes, [StepOutcome::Moved]);
+        assert_eq!(body(&sim), [Position { x: 3, y: 4 }, Position { x: 3, y: 3 }]);
+
+        let report = sim.tick(&[Direction::Up]);
+        assert_eq!(report.outcomes, [StepOutcome::Ate(Position { x: 3, y: 5 })]);
+        assert_eq!(body(&sim), [Position { x: 3, y: 5 }, Position { x: 3, y: 4 }, Position { x: 3, y: 3 }]);
+        assert_eq!(sim.snake(0).score(), Rules::default().score_multiplier);
+        assert!(sim.food().is_empty());
+
+        // The next move drags the longer body along
+        sim.tick(&[Direction::Right]);
+        assert_eq!(body(&sim), [Position { x: 4, y: 5 }, Position { x: 3, y: 5 }, Position { x: 3, y: 4 }]);
+    }
+
+    #[test]
+    fn turning_back_is_ignored() {
+        let mut sim = walled();
+        sim.tick(&[Direction::Down]);
+        assert_eq!(sim.snake(0).direction(), Direction::Up);
+        assert_eq!(sim.snake(0).head(), Position { x: 3, y: 4 });
+    }
+
+    #[test]
+    fn the_border_and_level_walls_kill() {
+        let mut sim = walled();
+        for _ in 0..3 {
+            assert_eq!(sim.tick(&[Direction::Left]).outcomes, [StepOutcome::Moved]);
+        }
+        let before = body(&sim);
+        assert_eq!(sim.tick(&[Direction::Left]).outcomes, [StepOutcome::Died(DeathCause::Border)]);
+        assert_eq!(body(&sim), before);
+        assert!(sim.is_over());
+
+        // Nothing moves once the game is over
+        assert!(sim.tick(&[Direction::Up]).outcomes.is_empty());
+        assert_eq!(sim.ticks(), 4);
+
+        let layout = Layout {
+            walls: HashSet::from([Position { x: 3, y: 5 }]),
+            ..Layout::default()
+        };
+        let mut sim = walled().with_layout(layout);
+        sim.tick(&[Direction::Up]);
+        assert_eq!(sim.tick(&[Direction::Up]).outcomes, [StepOutcome::Died(DeathCause::Border)]);
+    }
+
+    #[test]
+    fn running_into_the_own_body_kills() {
+        let grid = GridConfig { start: Position { x: 3, y: 6 }, start_length: 5, ..GridConfig::default() };
+        let mut sim = SnakeSim::new(grid, Rules::default(), 1);
+
+        sim.tick(&[Direction::Right]);
+        sim.tick(&[Direction::Down]);
+        assert_eq!(sim.tick(&[Direction::Left]).outcomes, [StepOutcome::Died(DeathCause::Tail)]);
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn the_tail_still_counts_on_the_tick_it_moves_away() {
+        // A snake of four circling a square of four tiles runs into its tail, one of three fits
+        for (length, outcome) in [(4, StepOutcome::Died(DeathCause::Tail)), (3, StepOutcome::Moved)] {
+            let grid = GridConfig { start: Position { x: 3, y: 5 }, start_length: length, ..GridConfig::default() };
+            let mut sim = SnakeSim::new(grid, Rules::default(), 1);
+
+            sim.tick(&[Direction::Right]);
+            sim.tick(&[Direction::Down]);
+            assert_eq!(sim.tick(&[Direction::Left]).outcomes, [outcome], "length {length}");
+        }
+    }
+
+    #[test]
+    fn a_wrap_board_brings_the_snake_in_on_the_other_side() {
+        let rules = Rules { board: BoardMode::Wrap, ..Rules::default() };
+        let mut sim = SnakeSim::new(GridConfig::default(), rules, 1);
+
+        for _ in 0..4 {
+            assert_eq!(sim.tick(&[Direction::Left]).outcomes, [StepOutcome::Moved]);
+        }
+        assert_eq!(sim.snake(0).head(), Position { x: 14, y: 3 });
+
+        for _ in 0..12 {
+            sim.tick(&[Direction::Up]);
+        }
+        assert_eq!(sim.snake(0).head(), Position { x: 14, y: 0 });
+        assert!(!sim.is_over());
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    io,
+    mem,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tungstenite::{
+    handshake::{server::NoCallback, HandshakeError, MidHandshake},
+    protocol::WebSocketConfig,
+    Message, ServerHandshake, WebSocket,
+};
+
+use crate::{
+    components::{Direction, Position},
+    net::Snapshot,
+    sim::{BoardMode, FoodItem, SnakeSim},
+};
+
+pub const DEFAULT_SPECTATOR_PORT: u16 = 7879;
+
+// Frames a spectator hasn't taken yet are kept up to this size, then it is dropped
+const MAX_BUFFERED: usize = 1024 * 1024;
+
+// Spectators only send pings and closes
+const MAX_INCOMING: usize = 64 * 1024;
+
+// Connections that don't finish the handshake in time are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Handshake = MidHandshake<ServerHandshake<TcpStream, NoCallback>>;
+
+// Board state spectators get as a JSON text message whenever it changes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frame {
+    pub tick: u32,
+    pub width: i32,
+    pub height: i32,
+    pub wrap: bool,
+    pub walls: Vec<Position>, // of the level, the edge of a walled board isn't listed
+    pub snakes: Vec<SnakeFrame>,
+    pub food: Vec<FoodItem>,
+    pub paused: bool,
+    pub over: bool,
+    pub winner: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnakeFrame {
+    pub name: String,
+    pub segments: Vec<Position>, // head first
+    pub direction: Direction,
+    pub score: u32,
+    pub alive: bool,
+}
+
+impl Frame {
+    // Frame of a local game, names in the order of the snakes
+    pub fn of_sim(sim: &SnakeSim, names: &[String]) -> Self {
+        let snakes = sim
+            .snakes()
+            .iter()
+            .zip(names)
+            .map(|(snake, name)| SnakeFrame {
+                name: name.clone(),
+                segments: snake.body().collect(),
+                direction: snake.direction(),
+                score: snake.score(),
+                alive: snake.is_alive(),
+            })
+            .collect();
+
+        // Sorted so that the same board always gives the same frame
+        let mut walls: Vec<_> = sim.layout().walls.iter().copied().collect();
+        walls.sort_by_key(|pos| (pos.y, pos.x));
+
+        Self {
+            tick: sim.ticks(),
+            width: sim.width(),
+            height: sim.height(),
+            wrap: sim.rules().board == BoardMode::Wrap,
+            walls,
+            snakes,
+            food: sim.food().to_vec(),
+            paused: false,
+            over: sim.is_over(),
+            winner: sim.winner(),
+        }
+    }
+
+    // Frame of an online game
+    pub fn of_snapshot(snapshot: &Snapshot) -> Self {
+        let snakes = snapshot
+            .snakes
+            .iter()
+            .map(|snake| SnakeFrame {
+                name: snake.name.clone(),
+                segments: snake.body.clone(),
+                direction: snake.direction,
+                score: snake.score,
+                alive: snake.alive,
+            })
+            .collect();
+
+        Self {
+            tick: snapshot.tick,
+            width: snapshot.grid.width,
+            height: snapshot.grid.height,
+            wrap: snapshot.rules.board == BoardMode::Wrap,
+            walls: snapshot.walls.clone(),
+            snakes,
+            food: snapshot.food.clone(),
+            paused: false,
+            over: snapshot.over,
+            winner: snapshot.winner,
+        }
+    }
+}
+
+// WebSocket endpoint that streams frames to any number of spectators. Nothing in
+// it blocks, so the game can drive it once per frame.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    handshakes: Vec<(Handshake, Instant)>, // with the time the connection came in
+    spectators: Vec<WebSocket<TcpStream>>,
+    latest: Option<String>, // sent to spectators as soon as they connect
+}
+
+impl SpectatorServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            handshakes: Vec::new(),
+            spectators: Vec::new(),
+            latest: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn spectators(&self) -> usize {
+        self.spectators.len()
+    }
+
+    // Accept new spectators, finish their handshakes and drop the ones that left
+    pub fn update(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.handshake(tungstenite::accept_with_config(stream, Some(socket_config())), now);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        for (handshake, started) in mem::take(&mut self.handshakes) {
+            if now.duration_since(started) < HANDSHAKE_TIMEOUT {
+                self.handshake(handshake.handshake(), started);
+            }
+        }
+
+        // Spectators only listen, reading answers pings and notices closed connections
+        self.spectators.retain_mut(|socket| loop {
+            match socket.read() {
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                    break still_connected(socket.flush());
+                }
+                Err(_) => break false,
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handshake(&mut self, result: Result<WebSocket<TcpStream>, HandshakeError<ServerHandshake<TcpStream, NoCallback>>>, started: Instant) {
+        match result {
+            Ok(mut socket) => {
+                let sent = match &self.latest {
+                    Some(frame) => send(&mut socket, frame),
+                    None => true,
+                };
+                if sent {
+                    self.spectators.push(socket);
+                }
+            }
+            Err(HandshakeError::Interrupted(handshake)) => self.handshakes.push((handshake, started)),
+            Err(HandshakeError::Failure(_)) => {}
+        }
+    }
+
+    // Send a frame to every spectator
+    pub fn publish(&mut self, frame: &Frame) {
+        let json = serde_json::to_string(frame).expect("frames serialize to JSON");
+        self.spectators.retain_mut(|socket| send(socket, &json));
+        self.latest = Some(json);
+    }
+}
+
+// Frames go out right away, the buffer only fills up while the spectator doesn't read
+fn socket_config() -> WebSocketConfig {
+    WebSocketConfig {
+        write_buffer_size: 0,
+        max_write_buffer_size: MAX_BUFFERED,
+        max_message_size: Some(MAX_INCOMING),
+        max_frame_size: Some(MAX_INCOMING),
+        ..WebSocketConfig::default()
+    }
+}
+
+// False once the spectator is gone or reads too slowly to keep up
+fn send(socket: &mut WebSocket<TcpStream>, json: &str) -> bool {
+    still_connected(socket.send(Message::text(json)))
+}
+
+// A full socket buffer is flushed on a later frame, until the frames that wait for
+// it pass MAX_BUFFERED
+fn still_connected(result: tungstenite::Result<()>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(tungstenite::Error::Io(err)) => err.kind() == io::ErrorKind::WouldBlock,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, thread};
+
+    use super::*;
+    use crate::sim::{GridConfig, Rules};
+
+    fn connect(port: u16) -> WebSocket<TcpStream> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let (socket, _) = tungstenite::client::client(format!("ws://127.0.0.1:{port}/"), stream).unwrap();
+        socket
+    }
+
+    // Update the server until the condition holds
+    fn wait_for(server: &mut SpectatorServer, done: impl Fn(&SpectatorServer) -> bool) {
+        for _ in 0..300 {
+            server.update().unwrap();
+            if done(server) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("timed out waiting");
+    }
+
+    #[test]
+    fn spectators_get_the_latest_frame_and_every_new_one() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let mut sim = SnakeSim::new(GridConfig::default(), Rules::default(), 5);
+        server.publish(&Frame::of_sim(&sim, &["Ann".to_string()]));
+
+        let spectator = thread::spawn(move || {
+            let mut socket = connect(port);
+            let frames: Vec<String> = (0..2).map(|_| socket.read().unwrap().into_text().unwrap().to_string()).collect();
+            socket.close(None).unwrap();
+            frames
+        });
+        wait_for(&mut server, |server| server.spectators() == 1);
+
+        let direction = sim.snake(0).direction();
+        sim.tick(&[direction]);
+        server.publish(&Frame::of_sim(&sim, &["Ann".to_string()]));
+
+        let frames = spectator.join().unwrap();
+        let first: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        let second: serde_json::Value = serde_json::from_str(&frames[1]).unwrap();
+        assert_eq!((first["tick"].as_u64(), second["tick"].as_u64()), (Some(0), Some(1)));
+        assert_eq!(second["snakes"][0]["name"], "Ann");
+
+        wait_for(&mut server, |server| server.spectators() == 0);
+    }
+
+    #[test]
+    fn spectators_that_stop_reading_are_dropped() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let connecting = thread::spawn(move || connect(port));
+        wait_for(&mut server, |server| server.spectators() == 1);
+        let _stalled = connecting.join().unwrap();
+
+        // Frames of a big board, far more than the socket buffers and MAX_BUFFERED together
+        let mut frame = Frame::of_sim(&SnakeSim::new(GridConfig::default(), Rules::default(), 5), &[]);
+        frame.walls = (0..10_000).map(|x| Position { x, y: x }).collect();
+        for tick in 0..1_000 {
+            frame.tick = tick;
+            server.publish(&frame);
+            server.update().unwrap();
+            if server.spectators() == 0 {
+                return;
+            }
+        }
+        panic!("the stalled spectator is still connected");
+    }
+
+    #[test]
+    fn unfinished_handshakes_time_out() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+        wait_for(&mut server, |server| server.handshakes.len() == 1);
+
+        server.handshakes[0].1 -= HANDSHAKE_TIMEOUT;
+        server.update().unwrap();
+        assert!(server.handshakes.is_empty());
+        assert_eq!(server.spectators(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+// Folder of the game in the platform data directory, the working directory when there is none
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir().map(|dir| dir.join("snakegame")).unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Format(String),
+    Version { found: u32, expected: u32 },
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Format(err) => write!(f, "malformed file: {err}"),
+            Self::Version { found, expected } => write!(f, "unsupported version {found}, expected {expected}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+pub fn parse_ron<T: DeserializeOwned>(text: &str) -> Result<T, StorageError> {
+    ron::from_str(text).map_err(|err| StorageError::Format(err.to_string()))
+}
+
+pub fn load_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, StorageError> {
+    parse_ron(&fs::read_to_string(path)?)
+}
+
+// Write a value as RON, creating the folders on the way
+pub fn save_ron<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), StorageError> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| StorageError::Format(err.to_string()))?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+// Load a file, starting from the default if it is missing or broken. A broken file is
+// moved aside to `.ron.bak` instead of being overwritten with the next save.
+pub fn load_or_backup<T: Default>(path: impl AsRef<Path>, what: &str, load: impl FnOnce(&Path) -> Result<T, StorageError>) -> T {
+    let path = path.as_ref();
+
+    match load(path) {
+        Ok(value) => value,
+        Err(StorageError::Io(err)) if err.kind() == io::ErrorKind::NotFound => T::default(),
+        Err(err) => {
+            let backup = path.with_extension("ron.bak");
+            eprintln!(
+                "snakegame: could not load {what} {}: {err}, moving it to {}",
+                path.display(),
+                backup.display(),
+            );
+            let _ = fs::rename(path, backup);
+            T::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_files_are_moved_aside() {
+        let dir = std::env::temp_dir().join(format!("snake_storage_{}", std::process::id()));
+        let path = dir.join("numbers.ron");
+        let backup = dir.join("numbers.ron.bak");
+        let _ = fs::remove_dir_all(&dir);
+
+        let load = |path: &Path| load_or_backup(path, "numbers", |path| load_ron::<Vec<u32>>(path));
+
+        // A missing file starts from the default and is only created by saving
+        assert!(load(&path).is_empty());
+        save_ron(&vec![1, 2, 3], &path).unwrap();
+        assert_eq!(load(&path), [1, 2, 3]);
+
+        fs::write(&path, "[1, 2,").unwrap();
+        assert!(matches!(load_ron::<Vec<u32>>(&path), Err(StorageError::Format(_))));
+        assert!(load(&path).is_empty());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "[1, 2,");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
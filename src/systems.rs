@@ -1,6 +1,17 @@
+pub mod ai;
+pub mod autopilot;
 pub mod collision;
+pub mod controls;
+pub mod editor;
+pub mod gamepad;
 pub mod input;
+pub mod leaderboard;
 pub mod movement;
+pub mod network;
+pub mod pause;
+pub mod pointer;
 pub mod rendering;
+pub mod replay;
 pub mod spawn;
+pub mod spectator;
 pub mod state;
\ No newline at end of file
@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+
+use crate::{
+    agent::BoardView,
+    components::{AiSnake, DirectionQueue, SnakeHead},
+    resources::Sim,
+};
+
+// Let every agent-driven snake pick its direction once per tick
+pub fn ai_input(
+    sim: Res<Sim>,
+    mut heads: Query<(&SnakeHead, &mut DirectionQueue, &mut AiSnake)>,
+) {
+    let tick = sim.0.ticks();
+
+    for (head, mut queue, mut ai) in &mut heads {
+        if ai.decided_tick == Some(tick) || !sim.0.snake(head.player).is_alive() {
+            continue;
+        }
+        ai.decided_tick = Some(tick);
+
+        let direction = ai.agent.next_direction(&BoardView::new(&sim.0, head.player));
+        queue.push(direction, head.direction);
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use crate::{
+    agent::AgentRegistry,
+    components::{AiSnake, AutopilotSnake, DirectionQueue, PathMarker, SnakeHead, Size},
+    resources::{Autopilot, GameSettings},
+    settings::Action,
+};
+
+const PATH_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.35);
+
+// Turn the autopilot on or off with its key
+pub fn autopilot_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    mut autopilot: ResMut<Autopilot>,
+) {
+    if settings.settings.key_map.just_pressed(Action::Autopilot, &keyboard_input) {
+        autopilot.enabled = !autopilot.enabled;
+    }
+}
+
+// Hand the snake of the first player to the agent or take it back
+pub fn apply_autopilot(
+    mut commands: Commands,
+    mut autopilot: ResMut<Autopilot>,
+    registry: Res<AgentRegistry>,
+    mut heads: Query<(Entity, Ref<SnakeHead>, &mut DirectionQueue, Has<AutopilotSnake>)>,
+) {
+    let Some((entity, head, mut queue, engaged)) = heads.iter_mut().find(|(_, head, _, _)| head.player == 0) else {
+        return;
+    };
+
+    // A new game starts without help
+    if head.is_added() {
+        autopilot.engaged = false;
+    }
+
+    if autopilot.enabled && !engaged {
+        let Some(agent) = registry.create(&autopilot.agent) else {
+            warn!("No agent named `{}`, turning the autopilot off", autopilot.agent);
+            autopilot.enabled = false;
+            return;
+        };
+        queue.0.clear();
+        commands.entity(entity).insert((AiSnake::new(agent), AutopilotSnake));
+    } else if !autopilot.enabled && engaged {
+        queue.0.clear();
+        commands.entity(entity).remove::<(AiSnake, AutopilotSnake)>();
+    }
+
+    if autopilot.enabled && !autopilot.engaged {
+        autopilot.engaged = true;
+    }
+}
+
+// Draw the path the autopilot plans to take over the grid
+pub fn update_path_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    autopilots: Query<Ref<AiSnake>, With<AutopilotSnake>>,
+    markers: Query<Entity, With<PathMarker>>,
+) {
+    let autopilot = autopilots.single().ok();
+
+    // The path only changes when the agent picks a new direction
+    if autopilot.as_ref().is_some_and(|ai| !ai.is_changed()) {
+        return;
+    }
+
+    for entity in &markers {
+        commands.entity(entity).despawn();
+    }
+
+    let Some(ai) = autopilot else {
+        return;
+    };
+    let mesh = meshes.add(Rectangle::new(1.0, 1.0));
+    let material = materials.add(PATH_COLOR);
+
+    for &position in ai.agent.path() {
+        commands.spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            PathMarker,
+            position,
+            Size::square(0.3),
+            Transform::default(),
+            GlobalTransform::default(),
+        ));
+    }
+}
@@ -1,102 +1,91 @@
 use bevy::prelude::*;
 
 use crate::{
-    components::{Position, Food, SnakeHead, Border},
-    resources::{GrowthEvent, GameOverEvent, SnakeSegments, LastTailPosition, NextHeadPosition, Score},
+    components::{Position, Food},
+    resources::{GrowthEvent, GameOverEvent, SnakeSegments, TickOutcome, Score, Sim},
+    sim::{Snake, StepOutcome},
     systems::spawn::spawn_segment,
 };
 
-// Detect collision with food
+// Apply the outcome of a movement tick to the snake and food entities
 pub fn snake_collision(
     mut commands: Commands,
 
-    mut next_reader: MessageReader<NextHeadPosition>,
+    mut outcome_reader: MessageReader<TickOutcome>,
     mut growth_writer: MessageWriter<GrowthEvent>,
     mut game_over_writer: MessageWriter<GameOverEvent>,
 
     mut positions: Query<&mut Position>,
-    head_query: Query<(Entity, &SnakeHead)>,
-    segments: ResMut<SnakeSegments>,
-    mut last_tail_position: ResMut<LastTailPosition>,
-    
+    segments: Res<SnakeSegments>,
+    sim: Res<Sim>,
+
     food_entities: Query<Entity, With<Food>>,
-    border_entities: Query<Entity, With<Border>>,
 
     mut score: ResMut<Score>,
 ) {
-    let (head_entity, _) = head_query.single().expect("SnakeHead not found");
-
-    // Read the next head movement (only one per tick)
-    let next = match next_reader.read().next() {
-        Some(NextHeadPosition(pos)) => *pos,
+    // Read the tick outcome (only one per tick)
+    let report = match outcome_reader.read().next() {
+        Some(TickOutcome(report)) => report,
         None => return, // no movement this frame
     };
 
-    // Store all segment postions before a move
-    let old_positions: Vec<Position> = segments
-        .0
-        .iter()
-        .map(|&entity| *positions.get(entity).unwrap())
-        .collect();
-
-    // Check for a border collision
-    for border_entity in &border_entities {
-        let border_pos = positions.get(border_entity).unwrap();
-        if *border_pos == next {
-            game_over_writer.write(GameOverEvent);
-            return;
+    for outcome in &report.outcomes {
+        if let StepOutcome::Ate(food_pos) = *outcome {
+            // Despawn the eaten food
+            for food_entity in &food_entities {
+                if *positions.get(food_entity).unwrap() == food_pos {
+                    commands.entity(food_entity).despawn();
+                }
+            }
+            growth_writer.write(GrowthEvent);
         }
     }
 
-    // Check for a tail collision
-    if old_positions.iter().skip(1).any(|&pos| pos == next) {
+    let scores: Vec<u32> = sim.0.snakes().iter().map(Snake::score).collect();
+    if score.0 != scores {
+        score.0 = scores;
+    }
+
+    if sim.0.is_over() {
         game_over_writer.write(GameOverEvent);
         return;
     }
 
-    // Check for a food collision
-    for food_entity in &food_entities {
-        let food_pos = positions.get(food_entity).unwrap();
-        if *food_pos == next {
-            commands.entity(food_entity).despawn();
-            growth_writer.write(GrowthEvent);
-
-            score.0 += 1;
+    // Every segment takes its position from the simulation
+    for (snake_segments, snake) in segments.0.iter().zip(sim.0.snakes()) {
+        for (&entity, sim_pos) in snake_segments.iter().zip(snake.body()) {
+            let mut pos = positions.get_mut(entity).unwrap();
+            *pos = sim_pos;
         }
     }
-
-    let mut head_pos = positions.get_mut(head_entity).unwrap();
-    *head_pos = next;
-
-    // Move tail segments, skip the first one since it's the head
-    for (i, &entity) in segments.0.iter().enumerate().skip(1) {
-        let mut pos = positions.get_mut(entity).unwrap();
-        *pos = old_positions[i - 1]; // every segment takes the position from the one before
-    }
-
-    // Store the last tail tile
-    last_tail_position.0 = old_positions.last().copied();
 }
 
-// Grow the snake by one segment upon a collision with food
+// Grow the snakes by one segment upon a collision with food
 pub fn snake_growth(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    last_tail_position: Res<LastTailPosition>,
+    sim: Res<Sim>,
     mut segments: ResMut<SnakeSegments>,
     mut growth_reader: MessageReader<GrowthEvent>,
 ) {
-    
-    for _event in growth_reader.read() {
-        if let Some(pos) = last_tail_position.0 {
+    if growth_reader.is_empty() {
+        return;
+    }
+    growth_reader.clear();
+
+    // Spawn the segments the simulation has grown since the last tick
+    for (player, (snake_segments, snake)) in segments.0.iter_mut().zip(sim.0.snakes()).enumerate() {
+        let new_positions: Vec<Position> = snake.body().skip(snake_segments.len()).collect();
+        for pos in new_positions {
             let new_segment = spawn_segment(
                 &mut commands,
                 &mut meshes,
                 &mut materials,
                 pos,
+                player,
             );
-            segments.0.push(new_segment);
+            snake_segments.push(new_segment);
         }
     }
-}
\ No newline at end of file
+}
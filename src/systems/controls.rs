@@ -0,0 +1,346 @@
+use bevy::prelude::*;
+
+use crate::{
+    resources::{GameSettings, PendingBinding},
+    settings::{Action, Key, KeyMap, KEYS_PER_ACTION},
+    systems::state::MenuButtonAction,
+};
+
+const CONFLICT_COLOR: Color = Color::srgb(0.9, 0.2, 0.2);
+
+#[derive(Component)]
+pub struct ControlsUI;
+
+#[derive(Component)]
+pub struct BindingButton {
+    pub action: Action,
+    pub slot: usize,
+}
+
+#[derive(Component)]
+pub struct BindingText {
+    pub action: Action,
+    pub slot: usize,
+}
+
+#[derive(Component)]
+pub struct ConflictText;
+
+#[derive(Component)]
+pub struct ResetBindingsButton;
+
+#[derive(Component)]
+pub struct SteeringButton;
+
+#[derive(Component)]
+pub struct SteeringText;
+
+type BindingButtonQuery<'a> = (
+    &'a Interaction,
+    &'a mut BackgroundColor,
+    Option<&'a BindingButton>,
+    Has<ResetBindingsButton>,
+    Has<SteeringButton>,
+);
+
+type BindingTextQuery<'a> = (&'a BindingText, &'a mut Text, &'a mut TextColor);
+
+// Build the controls screen
+pub fn setup_controls_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    mut pending: ResMut<PendingBinding>,
+) {
+    let font = asset_server.load("fonts/FiraSans.ttf");
+    let key_map = &settings.settings.key_map;
+    pending.0 = None;
+
+    let text_font = |size: f32| TextFont {
+        font: font.clone(),
+        font_size: size,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            ControlsUI,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(8.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                ))
+                .with_children(|panel| {
+                    // Title
+                    panel.spawn((
+                        Text::new("Controls"),
+                        text_font(40.0),
+                        TextColor(Color::WHITE),
+                    ));
+
+                    panel.spawn((
+                        Text::new("Click a binding, then press a key to bind it. Delete clears it.\nIn versus games the first column steers player 1 and the second player 2."),
+                        text_font(16.0),
+                        TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                    ));
+
+                    // Absolute or relative steering
+                    panel.spawn((
+                        Button,
+                        SteeringButton,
+                        Node {
+                            padding: UiRect::all(Val::Px(8.0)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                        ))
+                        .with_children(|button| {
+                            button.spawn((
+                                SteeringText,
+                                Text::new(steering_label(&settings)),
+                                text_font(20.0),
+                                TextColor(Color::WHITE),
+                            ));
+                        });
+
+                    // One row per action with a button for each key slot
+                    for action in Action::ALL {
+                        panel
+                            .spawn(Node {
+                                align_items: AlignItems::Center,
+                                column_gap: Val::Px(8.0),
+                                ..default()
+                            })
+                            .with_children(|row| {
+                                row.spawn((
+                                    Text::new(action.name()),
+                                    text_font(20.0),
+                                    TextColor(Color::WHITE),
+                                    Node {
+                                        width: Val::Px(120.0),
+                                        ..default()
+                                    },
+                                ));
+
+                                for slot in 0..KEYS_PER_ACTION {
+                                    row.spawn((
+                                        Button,
+                                        BindingButton { action, slot },
+                                        Node {
+                                            width: Val::Px(200.0),
+                                            padding: UiRect::all(Val::Px(6.0)),
+                                            justify_content: JustifyContent::Center,
+                                            ..default()
+                                        },
+                                        BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                                        ))
+                                        .with_children(|button| {
+                                            button.spawn((
+                                                BindingText { action, slot },
+                                                Text::new(binding_label(key_map, action, slot)),
+                                                text_font(18.0),
+                                                TextColor(Color::WHITE),
+                                            ));
+                                        });
+                                }
+                            });
+                    }
+
+                    // Keys that trigger more than one action
+                    panel.spawn((
+                        ConflictText,
+                        Text::new(""),
+                        text_font(16.0),
+                        TextColor(CONFLICT_COLOR),
+                    ));
+
+                    let buttons = [
+                        (Some(MenuButtonAction::QuitToMenu), "Back"),
+                        (None, "Reset to defaults"),
+                    ];
+
+                    for (action, label) in buttons {
+                        let mut button = panel.spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(200.0),
+                                padding: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                        ));
+                        match action {
+                            Some(action) => button.insert(action),
+                            None => button.insert(ResetBindingsButton),
+                        };
+                        button.with_children(|button| {
+                            button.spawn((
+                                Text::new(label),
+                                text_font(22.0),
+                                TextColor(Color::WHITE),
+                            ));
+                        });
+                    }
+                });
+        });
+}
+
+// Remove the controls screen
+pub fn cleanup_controls_screen(
+    mut commands: Commands,
+    mut pending: ResMut<PendingBinding>,
+    roots: Query<Entity, With<ControlsUI>>,
+) {
+    pending.0 = None;
+
+    for entity in &roots {
+        commands.entity(entity).despawn();
+    }
+}
+
+// Pick the binding to change, reset all of them or switch the steering mode
+pub fn binding_button_system(
+    mut settings: ResMut<GameSettings>,
+    mut pending: ResMut<PendingBinding>,
+    mut interaction_query: Query<BindingButtonQuery, Changed<Interaction>>,
+) {
+    for (interaction, mut color, binding, reset, steering) in &mut interaction_query {
+        let handled = binding.is_some() || reset || steering;
+
+        match *interaction {
+            Interaction::Pressed => {
+                *color = BackgroundColor(Color::srgb(0.5, 0.5, 0.5));
+
+                if let Some(binding) = binding {
+                    // Clicking the waiting binding again cancels it
+                    let slot = Some((binding.action, binding.slot));
+                    pending.0 = if pending.0 == slot { None } else { slot };
+                } else if reset {
+                    pending.0 = None;
+                    settings.settings.key_map = KeyMap::default();
+                    save_settings(&settings);
+                } else if steering {
+                    settings.settings.steering = settings.settings.steering.toggle();
+                    save_settings(&settings);
+                }
+            }
+            Interaction::Hovered if handled => {
+                *color = BackgroundColor(Color::WHITE);
+            }
+            Interaction::None if handled => {
+                *color = BackgroundColor(Color::srgb(0.2, 0.2, 0.2));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Bind the next key press to the waiting binding
+pub fn capture_binding(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<GameSettings>,
+    mut pending: ResMut<PendingBinding>,
+) {
+    let Some((action, slot)) = pending.0 else {
+        return;
+    };
+
+    for &code in keyboard_input.get_just_pressed() {
+        let key = if code == KeyCode::Delete {
+            None
+        } else if let Some(key) = Key::new(code) {
+            Some(key)
+        } else {
+            // Keys without a name can't be saved
+            continue;
+        };
+
+        settings.settings.key_map.bind(action, slot, key);
+        save_settings(&settings);
+        pending.0 = None;
+        return;
+    }
+}
+
+// Show the bound keys and flag the ones that are bound twice
+pub fn update_binding_texts(
+    settings: Res<GameSettings>,
+    pending: Res<PendingBinding>,
+    mut binding_texts: Query<BindingTextQuery, (Without<ConflictText>, Without<SteeringText>)>,
+    mut conflict_text: Query<&mut Text, (With<ConflictText>, Without<SteeringText>)>,
+    mut steering_text: Query<&mut Text, With<SteeringText>>,
+) {
+    if !settings.is_changed() && !pending.is_changed() {
+        return;
+    }
+    let key_map = &settings.settings.key_map;
+
+    for mut text in &mut steering_text {
+        text.0 = steering_label(&settings);
+    }
+
+    for (binding, mut text, mut color) in &mut binding_texts {
+        let key = key_map.keys(binding.action)[binding.slot];
+
+        text.0 = if pending.0 == Some((binding.action, binding.slot)) {
+            "press a key to bind".to_string()
+        } else {
+            binding_label(key_map, binding.action, binding.slot)
+        };
+        color.0 = if key.is_some_and(|key| key_map.is_conflict(key)) {
+            CONFLICT_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+
+    let mut conflicts: Vec<_> = key_map
+        .bindings
+        .values()
+        .flatten()
+        .flatten()
+        .filter(|&&key| key_map.is_conflict(key))
+        .map(|key| key.name())
+        .collect();
+    conflicts.sort_unstable();
+    conflicts.dedup();
+
+    if let Ok(mut text) = conflict_text.single_mut() {
+        text.0 = if conflicts.is_empty() {
+            String::new()
+        } else {
+            format!("Bound to more than one action: {}", conflicts.join(", "))
+        };
+    }
+}
+
+fn binding_label(key_map: &KeyMap, action: Action, slot: usize) -> String {
+    key_map.keys(action)[slot].map_or("-".to_string(), |key| key.name().to_string())
+}
+
+fn steering_label(settings: &GameSettings) -> String {
+    format!("Steering: {}", settings.settings.steering.name())
+}
+
+pub fn save_settings(settings: &GameSettings) {
+    if let Err(err) = settings.settings.save(&settings.path) {
+        warn!("Could not save settings to {}: {err}", settings.path.display());
+    }
+}
@@ -0,0 +1,510 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+
+use crate::{
+    components::{Direction, GameEntityFilter, Size},
+    level::{Level, Tile},
+    resources::{EditorTool, GameState, LevelEditor, Levels, SnakeSegments},
+    sim::{Goal, GridConfig},
+    systems::{
+        pause::PauseUI,
+        rendering::{spawn_borders, spawn_walls, tile_at},
+        spawn::spawn_segment,
+        state::{cleanup_score_ui, spawn_head, GameOverUI, MenuButtonAction},
+    },
+};
+
+const FOOD_ZONE_COLOR: Color = Color::srgba(0.2, 0.6, 0.2, 0.4);
+const BUTTON_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+const HOVERED_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+const SELECTED_COLOR: Color = Color::srgb(0.0, 0.5, 0.0);
+
+#[derive(Component)]
+pub struct EditorUI;
+
+#[derive(Component)]
+pub struct EditorStatus;
+
+// Food zone tile on the board of the editor
+#[derive(Component)]
+pub struct EditorTile;
+
+// Everything drawn on the board of the editor
+type EditorBoardFilter = Or<(With<EditorTile>, GameEntityFilter)>;
+
+// What an editor button or its key does
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum EditorAction {
+    Tool(EditorTool),
+    Narrower,
+    Wider,
+    Shorter,
+    Taller,
+    CycleGoal,
+    New,
+    NextLevel,
+    Save,
+    TestPlay,
+    Menu,
+}
+
+// Buttons along the bottom of the editor with the key that does the same
+const EDITOR_BUTTONS: [(EditorAction, &str, KeyCode); 14] = [
+    (EditorAction::Tool(EditorTool::Wall), "Wall 1", KeyCode::Digit1),
+    (EditorAction::Tool(EditorTool::Floor), "Floor 2", KeyCode::Digit2),
+    (EditorAction::Tool(EditorTool::FoodZone), "Food zone 3", KeyCode::Digit3),
+    (EditorAction::Tool(EditorTool::Start), "Start 4", KeyCode::Digit4),
+    (EditorAction::Narrower, "Width - -", KeyCode::Minus),
+    (EditorAction::Wider, "Width + =", KeyCode::Equal),
+    (EditorAction::Shorter, "Height - [", KeyCode::BracketLeft),
+    (EditorAction::Taller, "Height + ]", KeyCode::BracketRight),
+    (EditorAction::CycleGoal, "Goal G", KeyCode::KeyG),
+    (EditorAction::New, "New N", KeyCode::KeyN),
+    (EditorAction::NextLevel, "Load L", KeyCode::KeyL),
+    (EditorAction::Save, "Save S", KeyCode::KeyS),
+    (EditorAction::TestPlay, "Test play T", KeyCode::KeyT),
+    (EditorAction::Menu, "Menu Esc", KeyCode::Escape),
+];
+
+// Everything an editor action changes
+#[derive(SystemParam)]
+pub struct EditorContext<'w> {
+    editor: ResMut<'w, LevelEditor>,
+    levels: ResMut<'w, Levels>,
+    next_state: ResMut<'w, NextState<GameState>>,
+}
+
+// Level editor screen, and the way back to it from a test play
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Editor), (setup_editor, cleanup_score_ui))
+            .add_systems(OnExit(GameState::Editor), cleanup_editor)
+            .add_systems(OnEnter(GameState::Menu), end_playtest)
+            .add_systems(OnEnter(GameState::GameOver), spawn_editor_button)
+            .add_systems(OnEnter(GameState::Paused), spawn_editor_button)
+            .add_systems(Update, (
+                (editor_button_system, editor_keyboard, editor_paint).chain(),
+                (sync_editor_board, update_editor_status, update_editor_buttons).after(editor_paint),
+            ).run_if(in_state(GameState::Editor)));
+    }
+}
+
+// Open the level in the editor or start a new one
+pub fn setup_editor(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut levels: ResMut<Levels>,
+    editor: Option<ResMut<LevelEditor>>,
+    grid_config: Res<GridConfig>,
+    mut segments: ResMut<SnakeSegments>,
+    game_entities: Query<Entity, GameEntityFilter>,
+) {
+    levels.playtest = None;
+
+    // Leftovers of a game or of a test play
+    for entity in &game_entities {
+        commands.entity(entity).despawn();
+    }
+    segments.0.clear();
+
+    // Coming back keeps the level, otherwise edit the selected one
+    match editor {
+        Some(mut editor) => editor.set_changed(),
+        None => {
+            let level = levels
+                .current()
+                .cloned()
+                .unwrap_or_else(|| Level::blank(new_level_name(&levels), grid_config.width, grid_config.height));
+            commands.insert_resource(LevelEditor::new(level));
+        }
+    }
+
+    let font = asset_server.load("fonts/FiraSans.ttf");
+
+    commands.spawn((
+        EditorUI,
+        EditorStatus,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        Text::new(""),
+        TextFont {
+            font: font.clone(),
+            font_size: 20.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+
+    commands
+        .spawn((
+            EditorUI,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.0),
+                width: Val::Percent(100.0),
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(6.0),
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (action, label, _) in EDITOR_BUTTONS {
+                parent
+                    .spawn((
+                        Button,
+                        action,
+                        Node {
+                            padding: UiRect::all(Val::Px(6.0)),
+                            ..default()
+                        },
+                        BackgroundColor(BUTTON_COLOR),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(label),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 16.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+                    });
+            }
+        });
+}
+
+// Remove the editor screen and the board
+pub fn cleanup_editor(
+    mut commands: Commands,
+    mut segments: ResMut<SnakeSegments>,
+    ui: Query<Entity, With<EditorUI>>,
+    board: Query<Entity, EditorBoardFilter>,
+) {
+    for entity in ui.iter().chain(&board) {
+        commands.entity(entity).despawn();
+    }
+    segments.0.clear();
+}
+
+// Draw the board again whenever the level changes
+pub fn sync_editor_board(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    editor: Res<LevelEditor>,
+    tiles: Query<Entity, EditorBoardFilter>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for entity in &tiles {
+        commands.entity(entity).despawn();
+    }
+
+    // The borders show the size of the board, even for a wrap-around game
+    let level = &editor.level;
+    spawn_borders(&mut commands, &mut meshes, &mut materials, &level.grid);
+    spawn_walls(&mut commands, &mut meshes, &mut materials, &level.layout.walls);
+
+    let mesh = meshes.add(Rectangle::new(1.0, 1.0));
+    let material = materials.add(FOOD_ZONE_COLOR);
+    for &pos in &level.layout.food_zone {
+        commands.spawn((
+            EditorTile,
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            pos,
+            Size::square(1.0),
+            Transform::default(),
+            GlobalTransform::default(),
+        ));
+    }
+
+    // The snake in its start pose, the parts that fit on the board
+    let grid = level.grid;
+    spawn_head(&mut commands, &mut meshes, &mut materials, grid.start, grid.start_direction, 0);
+    for pos in grid.start_body(0).skip(1).filter(|&pos| grid.contains(pos)) {
+        spawn_segment(&mut commands, &mut meshes, &mut materials, pos, 0);
+    }
+}
+
+// Name, size, goal and tool of the level and what is wrong with it
+pub fn update_editor_status(editor: Res<LevelEditor>, mut query: Query<&mut Text, With<EditorStatus>>) {
+    if !editor.is_changed() {
+        return;
+    }
+    let Ok(mut text) = query.single_mut() else {
+        return;
+    };
+
+    let level = &editor.level;
+    let unsaved = if editor.unsaved { " (unsaved)" } else { "" };
+    let problem = match level.validate(1) {
+        Ok(()) => String::new(),
+        Err(err) => format!("\nCan't play: {err}"),
+    };
+
+    text.0 = format!(
+        "{}{unsaved}   {}x{}   Goal: {}   Tool: {}\nLeft click paints, right click erases, arrows turn the snake\n{}{problem}",
+        level.name,
+        level.grid.width,
+        level.grid.height,
+        goal_label(level.layout.goal),
+        editor.tool.name(),
+        editor.status,
+    );
+}
+
+// Paint tiles and place the snake with the mouse
+pub fn editor_paint(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    buttons: Query<&Interaction, With<Button>>,
+    mut editor: ResMut<LevelEditor>,
+) {
+    // Clicks on the buttons don't reach the board
+    if buttons.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+    let Ok(window) = windows.single() else {
+        return;
+    };
+    let Some(pos) = window
+        .cursor_position()
+        .and_then(|cursor| cameras.iter().find_map(|(camera, transform)| camera.viewport_to_world_2d(transform, cursor).ok()))
+        .and_then(|point| tile_at(window, &editor.level.grid, point))
+    else {
+        return;
+    };
+
+    let tile = if mouse_input.pressed(MouseButton::Right) {
+        Tile::Floor
+    } else if mouse_input.pressed(MouseButton::Left) {
+        match editor.tool {
+            EditorTool::Wall => Tile::Wall,
+            EditorTool::Floor => Tile::Floor,
+            EditorTool::FoodZone => Tile::FoodZone,
+            EditorTool::Start => {
+                if mouse_input.just_pressed(MouseButton::Left) {
+                    let grid = editor.level.grid;
+                    let direction = if pos == grid.start { grid.start_direction.turn_right() } else { grid.start_direction };
+                    editor.level.set_start(pos, direction);
+                    editor.unsaved = true;
+                }
+                return;
+            }
+        }
+    } else {
+        return;
+    };
+
+    // Only real changes redraw the board, the start tile stays floor
+    if editor.level.tile(pos) != tile && pos != editor.level.grid.start {
+        editor.level.set_tile(pos, tile);
+        editor.unsaved = true;
+    }
+}
+
+// Carry out the editor buttons
+pub fn editor_button_system(
+    mut context: EditorContext,
+    interaction_query: Query<(&Interaction, &EditorAction), Changed<Interaction>>,
+) {
+    for (interaction, &action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            apply_editor_action(action, &mut context);
+        }
+    }
+}
+
+// Show the selected tool and the button under the pointer
+pub fn update_editor_buttons(editor: Res<LevelEditor>, mut buttons: Query<(&Interaction, &mut BackgroundColor, &EditorAction)>) {
+    for (interaction, mut color, &action) in &mut buttons {
+        let wanted = match interaction {
+            _ if action == EditorAction::Tool(editor.tool) => SELECTED_COLOR,
+            Interaction::None => BUTTON_COLOR,
+            _ => HOVERED_COLOR,
+        };
+        if color.0 != wanted {
+            color.0 = wanted;
+        }
+    }
+}
+
+// Editor keys, the arrows turn the snake
+pub fn editor_keyboard(keyboard_input: Res<ButtonInput<KeyCode>>, mut context: EditorContext) {
+    for (action, _, key) in EDITOR_BUTTONS {
+        if keyboard_input.just_pressed(key) {
+            apply_editor_action(action, &mut context);
+        }
+    }
+
+    let turns = [
+        (KeyCode::ArrowUp, Direction::Up),
+        (KeyCode::ArrowDown, Direction::Down),
+        (KeyCode::ArrowLeft, Direction::Left),
+        (KeyCode::ArrowRight, Direction::Right),
+    ];
+    for (key, direction) in turns {
+        if keyboard_input.just_pressed(key) && context.editor.level.grid.start_direction != direction {
+            let start = context.editor.level.grid.start;
+            context.editor.level.set_start(start, direction);
+            context.editor.unsaved = true;
+        }
+    }
+}
+
+fn apply_editor_action(action: EditorAction, context: &mut EditorContext) {
+    let EditorContext { editor, levels, next_state } = context;
+    let grid = editor.level.grid;
+
+    match action {
+        EditorAction::Tool(tool) => editor.tool = tool,
+        EditorAction::Narrower | EditorAction::Wider | EditorAction::Shorter | EditorAction::Taller => {
+            let (width, height) = match action {
+                EditorAction::Narrower => (grid.width - 1, grid.height),
+                EditorAction::Wider => (grid.width + 1, grid.height),
+                EditorAction::Shorter => (grid.width, grid.height - 1),
+                _ => (grid.width, grid.height + 1),
+            };
+            editor.level.resize(width, height);
+            editor.unsaved |= editor.level.grid != grid;
+        }
+        EditorAction::CycleGoal => {
+            editor.level.layout.goal = match editor.level.layout.goal {
+                Goal::Endless => Goal::Score(20),
+                Goal::Score(_) => Goal::Length(20),
+                Goal::Length(_) => Goal::Survive(60),
+                Goal::Survive(_) => Goal::Endless,
+            };
+            editor.unsaved = true;
+        }
+        EditorAction::New => {
+            let level = Level::blank(new_level_name(levels), grid.width, grid.height);
+            editor.status = format!("New level {}", level.name);
+            editor.level = level;
+            editor.unsaved = false;
+        }
+        EditorAction::NextLevel => {
+            // The level after the one in the editor, unsaved changes are dropped
+            let current = levels.levels.iter().position(|level| level.path.is_some() && level.path == editor.level.path);
+            let next = current.map_or(0, |index| (index + 1) % levels.levels.len().max(1));
+            match levels.levels.get(next) {
+                Some(level) => {
+                    editor.level = level.clone();
+                    editor.unsaved = false;
+                    editor.status = format!("Loaded {}", level.name);
+                }
+                None => editor.status = format!("No levels in {}", levels.dir.display()),
+            }
+        }
+        EditorAction::Save => {
+            let path = editor.level.path.clone().unwrap_or_else(|| free_path(&levels.dir, &editor.level.file_name()));
+            match editor.level.save(&path) {
+                Ok(()) => {
+                    // New levels show up on the menu right away
+                    match levels.levels.iter_mut().find(|level| level.path.as_ref() == Some(&path)) {
+                        Some(level) => *level = editor.level.clone(),
+                        None => levels.levels.push(editor.level.clone()),
+                    }
+                    editor.unsaved = false;
+                    editor.status = format!("Saved to {}", path.display());
+                }
+                Err(err) => editor.status = format!("Not saved: {err}"),
+            }
+        }
+        EditorAction::TestPlay => match editor.level.validate(1) {
+            Ok(()) => {
+                levels.playtest = Some(editor.level.clone());
+                next_state.set(GameState::Playing);
+            }
+            Err(err) => editor.status = format!("Can't play: {err}"),
+        },
+        EditorAction::Menu => next_state.set(GameState::Menu),
+    }
+}
+
+// A way back to the editor on the pause and game over screens of a test play
+pub fn spawn_editor_button(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+    state: Res<State<GameState>>,
+) {
+    if levels.playtest.is_none() {
+        return;
+    }
+
+    let mut button = commands.spawn((
+        Button,
+        MenuButtonAction::OpenEditor,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(BUTTON_COLOR),
+    ));
+    match state.get() {
+        GameState::Paused => button.insert(PauseUI),
+        _ => button.insert(GameOverUI),
+    };
+    button.with_children(|button| {
+        button.spawn((
+            Text::new("Back to the editor"),
+            TextFont {
+                font: asset_server.load("fonts/FiraSans.ttf"),
+                font_size: 20.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+    });
+}
+
+// Games started from the menu play the selected level again
+pub fn end_playtest(mut levels: ResMut<Levels>) {
+    if levels.playtest.is_some() {
+        levels.playtest = None;
+    }
+}
+
+fn goal_label(goal: Goal) -> String {
+    match goal {
+        Goal::Endless => "Endless".to_string(),
+        Goal::Score(score) => format!("Score {score}"),
+        Goal::Length(length) => format!("Length {length}"),
+        Goal::Survive(seconds) => format!("Survive {seconds} s"),
+    }
+}
+
+// First `Custom N` no level is called yet
+fn new_level_name(levels: &Levels) -> String {
+    (1..)
+        .map(|number| format!("Custom {number}"))
+        .find(|name| !levels.levels.iter().any(|level| level.name.eq_ignore_ascii_case(name)))
+        .expect("some name is free")
+}
+
+// Path in the level directory that no file uses yet
+fn free_path(dir: &Path, file_name: &str) -> PathBuf {
+    let path = dir.join(file_name);
+    let stem = file_name.trim_end_matches(".ron");
+    std::iter::once(path)
+        .chain((2..).map(|number| dir.join(format!("{stem}_{number}.ron"))))
+        .find(|path| !path.exists())
+        .expect("some file name is free")
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, ui::UiGlobalTransform};
+
+use crate::{
+    components::{AiSnake, SnakeHead, Direction, DirectionQueue},
+    resources::{GameState, ResumeCountdown},
+    systems::{
+        pause::resume_game,
+        state::{apply_menu_action, MenuButtonAction, MenuOptions},
+    },
+};
+
+// Stick deflection below this is ignored
+const STICK_DEADZONE: f32 = 0.5;
+
+const FOCUS_COLOR: Color = Color::srgb(0.0, 0.8, 0.0);
+
+// Direction of a stick along its dominant axis, outside of the deadzone
+pub fn stick_direction(stick: Vec2) -> Option<Direction> {
+    (stick.length() >= STICK_DEADZONE).then(|| Direction::dominant(stick))
+}
+
+// Direction of a D-pad button pressed this frame
+fn dpad_direction(gamepad: &Gamepad) -> Option<Direction> {
+    if gamepad.just_pressed(GamepadButton::DPadUp) {
+        Some(Direction::Up)
+    } else if gamepad.just_pressed(GamepadButton::DPadDown) {
+        Some(Direction::Down)
+    } else if gamepad.just_pressed(GamepadButton::DPadLeft) {
+        Some(Direction::Left)
+    } else if gamepad.just_pressed(GamepadButton::DPadRight) {
+        Some(Direction::Right)
+    } else {
+        None
+    }
+}
+
+// Direction the gamepad was pushed into this frame, the stick only counts once per push
+fn pushed_direction(
+    entity: Entity,
+    gamepad: &Gamepad,
+    sticks: &mut HashMap<Entity, Option<Direction>>,
+) -> Option<Direction> {
+    let stick = stick_direction(gamepad.left_stick());
+    let previous = sticks.insert(entity, stick).flatten();
+
+    dpad_direction(gamepad).or(if stick != previous { stick } else { None })
+}
+
+// Steer the snake with the D-pad or the left stick, each gamepad steers its own snake in versus
+pub fn gamepad_input(
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut heads: Query<(&SnakeHead, &mut DirectionQueue), Without<AiSnake>>,
+    mut sticks: Local<HashMap<Entity, Option<Direction>>>,
+) {
+    let versus = heads.iter().count() > 1;
+
+    // Connection order decides which snake a gamepad steers
+    let mut gamepads: Vec<_> = gamepads.iter().collect();
+    gamepads.sort_by_key(|(entity, _)| *entity);
+
+    for (index, (entity, gamepad)) in gamepads.into_iter().enumerate() {
+        let Some(direction) = pushed_direction(entity, gamepad, &mut sticks) else {
+            continue;
+        };
+        let player = if versus { index } else { 0 };
+
+        for (head, mut queue) in &mut heads {
+            if head.player == player {
+                queue.push(direction, head.direction);
+            }
+        }
+    }
+}
+
+// Move the focus through the menu buttons and press them with A, B goes back
+pub fn gamepad_menu_navigation(
+    mut commands: Commands,
+    gamepads: Query<(Entity, &Gamepad)>,
+    buttons: Query<(Entity, &MenuButtonAction, &UiGlobalTransform)>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut options: MenuOptions,
+    mut countdown: ResMut<ResumeCountdown>,
+    mut focus: Local<Option<Entity>>,
+    mut sticks: Local<HashMap<Entity, Option<Direction>>>,
+) {
+    // Buttons in screen order, top to bottom
+    let mut order: Vec<_> = buttons.iter().collect();
+    order.sort_by(|(_, _, a), (_, _, b)| {
+        a.translation.y.total_cmp(&b.translation.y).then(a.translation.x.total_cmp(&b.translation.x))
+    });
+
+    // The focused button went away with its screen
+    let mut index = focus.and_then(|entity| order.iter().position(|(button, _, _)| *button == entity));
+
+    for (entity, gamepad) in &gamepads {
+        match pushed_direction(entity, gamepad, &mut sticks) {
+            Some(Direction::Down | Direction::Right) if !order.is_empty() => {
+                index = Some(index.map_or(0, |i| (i + 1) % order.len()));
+            }
+            Some(Direction::Up | Direction::Left) if !order.is_empty() => {
+                index = Some(index.map_or(0, |i| (i + order.len() - 1) % order.len()));
+            }
+            _ => {}
+        }
+
+        if gamepad.just_pressed(GamepadButton::South) {
+            if let Some((_, action, _)) = index.map(|i| order[i]) {
+                apply_menu_action(*action, &mut next_state, &mut options, &mut countdown);
+            }
+        } else if gamepad.just_pressed(GamepadButton::East) {
+            match state.get() {
+                GameState::Paused => resume_game(&mut next_state, &mut countdown),
+                GameState::GameOver | GameState::Controls => next_state.set(GameState::Menu),
+                _ => {}
+            }
+        }
+    }
+
+    let focused = index.map(|i| order[i].0);
+    if focused == *focus {
+        return;
+    }
+
+    // Outline the focused button
+    if let Some(entity) = *focus
+        && let Ok(mut button) = commands.get_entity(entity)
+    {
+        button.remove::<Outline>();
+    }
+    if let Some(entity) = focused {
+        commands
+            .entity(entity)
+            .insert(Outline::new(Val::Px(3.0), Val::Px(2.0), FOCUS_COLOR));
+    }
+    *focus = focused;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        input::{
+            gamepad::{GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent, RawGamepadButtonChangedEvent, RawGamepadEvent},
+            InputPlugin,
+        },
+        math::Affine2,
+        state::app::StatesPlugin,
+    };
+
+    use super::*;
+    use crate::{
+        agent::AgentRegistry,
+        resources::{Autopilot, GameRules, GameSeed, GameSettings, Levels, PlayerCount, Rivals},
+        settings::Settings,
+        systems::pause::pause_controls,
+    };
+
+    // App with the input plugin and a connected gamepad, the systems under test run in Update
+    fn app(state: GameState) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((InputPlugin, StatesPlugin))
+            .insert_state(state)
+            .init_resource::<ResumeCountdown>()
+            .init_resource::<GameRules>()
+            .init_resource::<PlayerCount>()
+            .init_resource::<Rivals>()
+            .init_resource::<Autopilot>()
+            .init_resource::<AgentRegistry>()
+            .init_resource::<Levels>()
+            .insert_resource(GameSeed::new(Some(1)))
+            .insert_resource(GameSettings {
+                settings: Settings::default(),
+                path: std::env::temp_dir().join("snake_gamepad_test.ron"),
+            });
+
+        let gamepad = app.world_mut().spawn_empty().id();
+        app.world_mut().write_message(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Test gamepad".to_string(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        app.update();
+        (app, gamepad)
+    }
+
+    fn stick(app: &mut App, gamepad: Entity, x: f32, y: f32) {
+        app.world_mut().write_message(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(gamepad, GamepadAxis::LeftStickX, x)));
+        app.world_mut().write_message(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(gamepad, GamepadAxis::LeftStickY, y)));
+        app.update();
+    }
+
+    // Press a button for one frame and let go of it in the next
+    fn tap(app: &mut App, gamepad: Entity, button: GamepadButton) {
+        for value in [1.0, 0.0] {
+            app.world_mut().write_message(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(gamepad, button, value)));
+            app.update();
+        }
+    }
+
+    fn state(app: &App) -> GameState {
+        *app.world().resource::<State<GameState>>().get()
+    }
+
+    fn queued(app: &mut App) -> Vec<Direction> {
+        let mut query = app.world_mut().query::<&DirectionQueue>();
+        query.single(app.world()).unwrap().0.iter().copied().collect()
+    }
+
+    #[test]
+    fn stick_direction_ignores_the_deadzone_and_picks_the_dominant_axis() {
+        assert_eq!(stick_direction(Vec2::new(0.3, 0.3)), None);
+        assert_eq!(stick_direction(Vec2::new(0.9, -0.4)), Some(Direction::Right));
+        assert_eq!(stick_direction(Vec2::new(-0.2, -0.6)), Some(Direction::Down));
+        assert_eq!(stick_direction(Vec2::new(-0.7, 0.1)), Some(Direction::Left));
+    }
+
+    #[test]
+    fn stick_steers_once_per_push() {
+        let (mut app, gamepad) = app(GameState::Playing);
+        app.add_systems(Update, gamepad_input);
+        app.world_mut().spawn((SnakeHead { direction: Direction::Up, player: 0 }, DirectionQueue::default()));
+
+        // A small push stays in the deadzone
+        stick(&mut app, gamepad, 0.3, 0.1);
+        assert!(queued(&mut app).is_empty());
+
+        // Holding the stick over several frames turns only once
+        stick(&mut app, gamepad, 0.9, -0.4);
+        app.update();
+        assert_eq!(queued(&mut app), [Direction::Right]);
+
+        stick(&mut app, gamepad, 0.0, 0.0);
+        stick(&mut app, gamepad, 0.2, 0.8);
+        assert_eq!(queued(&mut app), [Direction::Right, Direction::Up]);
+    }
+
+    #[test]
+    fn start_and_select_toggle_the_pause() {
+        let (mut app, gamepad) = app(GameState::Playing);
+        app.add_systems(Update, pause_controls);
+
+        tap(&mut app, gamepad, GamepadButton::Start);
+        assert_eq!(state(&app), GameState::Paused);
+        assert!(app.world().resource::<ResumeCountdown>().0.is_none());
+
+        tap(&mut app, gamepad, GamepadButton::Select);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(app.world().resource::<ResumeCountdown>().0.is_some());
+    }
+
+    #[test]
+    fn a_presses_the_focused_button_and_b_goes_back() {
+        let (mut app, gamepad) = app(GameState::Menu);
+        app.add_systems(Update, gamepad_menu_navigation);
+        let button = |y| UiGlobalTransform::from(Affine2::from_translation(Vec2::new(0.0, y)));
+        let controls = app.world_mut().spawn((MenuButtonAction::Controls, button(10.0))).id();
+        app.world_mut().spawn((MenuButtonAction::Play, button(50.0)));
+
+        // Nothing has the focus until the D-pad moves it
+        tap(&mut app, gamepad, GamepadButton::South);
+        assert_eq!(state(&app), GameState::Menu);
+
+        tap(&mut app, gamepad, GamepadButton::DPadDown);
+        assert!(app.world().entity(controls).contains::<Outline>());
+        tap(&mut app, gamepad, GamepadButton::South);
+        assert_eq!(state(&app), GameState::Controls);
+
+        tap(&mut app, gamepad, GamepadButton::East);
+        assert_eq!(state(&app), GameState::Menu);
+    }
+
+    #[test]
+    fn b_resumes_a_paused_game() {
+        let (mut app, gamepad) = app(GameState::Paused);
+        app.add_systems(Update, gamepad_menu_navigation);
+
+        tap(&mut app, gamepad, GamepadButton::East);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(app.world().resource::<ResumeCountdown>().0.is_some());
+    }
+}
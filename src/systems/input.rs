@@ -1,38 +1,82 @@
 use bevy::prelude::*;
 
 use crate::{
-    components::{SnakeHead, Direction},
-    resources::DirectionLocked,
+    components::{AiSnake, SnakeHead, DirectionQueue},
+    resources::GameSettings,
+    settings::{Action, SteeringMode},
 };
 
-// Read the player input and update the snake direction
+// Snake steered by a key slot, each slot has its own snake in versus games
+fn slot_player(slot: usize, versus: bool) -> usize {
+    if versus { slot } else { 0 }
+}
+
+// Read the player input and queue the turns of the snakes
 pub fn input(
     keyboard_input: Res<ButtonInput<KeyCode>>, 
-    mut heads: Query<&mut SnakeHead>,
-    mut dir_lock: ResMut<DirectionLocked>,
+    settings: Res<GameSettings>,
+    mut heads: Query<(&SnakeHead, &mut DirectionQueue), Without<AiSnake>>,
 ) {
-    let mut head = heads.single_mut().expect("SnakeHead not found");
+    if settings.settings.steering != SteeringMode::Absolute {
+        return;
+    }
+
+    let versus = heads.iter().count() > 1;
+
+    // Several keys can be pressed in the same frame
+    for &key in keyboard_input.get_just_pressed() {
+        for (action, slot) in settings.settings.key_map.slot_actions(key) {
+            let Some(new_dir) = action.direction() else {
+                continue;
+            };
+            let player = slot_player(slot, versus);
+
+            for (head, mut queue) in &mut heads {
+                if head.player == player {
+                    queue.push(new_dir, head.direction);
+                }
+            }
+        }
+    }
+}
 
-    // Ignore the input if there is a direction lock
-    if dir_lock.0 {
+// Rotate the snakes relative to their heading with two keys or the mouse buttons
+pub fn relative_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    settings: Res<GameSettings>,
+    mut heads: Query<(&SnakeHead, &mut DirectionQueue), Without<AiSnake>>,
+) {
+    if settings.settings.steering != SteeringMode::Relative {
         return;
     }
 
-    let new_dir = if keyboard_input.just_pressed(KeyCode::ArrowLeft) || keyboard_input.just_pressed(KeyCode::KeyA) {
-            Direction::Left
-        } else if keyboard_input.just_pressed(KeyCode::ArrowRight) || keyboard_input.just_pressed(KeyCode::KeyD) {
-            Direction::Right
-        } else if keyboard_input.just_pressed(KeyCode::ArrowDown) || keyboard_input.just_pressed(KeyCode::KeyS) {
-            Direction::Down
-        } else if keyboard_input.just_pressed(KeyCode::ArrowUp) || keyboard_input.just_pressed(KeyCode::KeyW) {
-            Direction::Up
-        } else {
-            return;
-        };
-
-    // Prevent turning into the opposite direction and crashing
-    if new_dir != head.direction.opposite() {
-        head.direction = new_dir;
-        dir_lock.0 = true;
+    let versus = heads.iter().count() > 1;
+
+    let mut rotations: Vec<(Action, usize)> = keyboard_input
+        .get_just_pressed()
+        .flat_map(|&key| settings.settings.key_map.slot_actions(key))
+        .filter(|(action, _)| matches!(action, Action::RotateLeft | Action::RotateRight))
+        .map(|(action, slot)| (action, slot_player(slot, versus)))
+        .collect();
+
+    // The mouse buttons always rotate the first player
+    if mouse_input.just_pressed(MouseButton::Left) {
+        rotations.push((Action::RotateLeft, 0));
+    }
+    if mouse_input.just_pressed(MouseButton::Right) {
+        rotations.push((Action::RotateRight, 0));
+    }
+
+    // Each rotation turns from the heading left by the queued turns
+    for (action, player) in rotations {
+        for (head, mut queue) in &mut heads {
+            if head.player != player {
+                continue;
+            }
+            if let Some(new_dir) = action.rotate(queue.heading(head.direction)) {
+                queue.push(new_dir, head.direction);
+            }
+        }
     }
-}
\ No newline at end of file
+}
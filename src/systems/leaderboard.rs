@@ -0,0 +1,112 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use crate::{
+    leaderboard::{game_mode, Leaderboard, LeaderboardEntry},
+    resources::{Autopilot, GameOverEvent, HighScores, Levels, PlayerName, Sim},
+    systems::state::player_name,
+};
+
+// Put the finished game on the leaderboard and save it
+pub fn record_score(
+    mut reader: MessageReader<GameOverEvent>,
+    sim: Res<Sim>,
+    autopilot: Res<Autopilot>,
+    levels: Res<Levels>,
+    own_name: Res<PlayerName>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    if reader.is_empty() {
+        return;
+    }
+    reader.clear();
+
+    // Solver games are not the player's and levels tried out in the editor may still change
+    if autopilot.engaged || levels.playtest.is_some() {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let humans = sim.0.humans();
+    let mode = game_mode(sim.0.rules().board, humans, sim.0.players() - humans);
+
+    // Every human snake of a versus game is ranked on its own
+    let mut inserted = false;
+    for player in 0..humans {
+        let snake = sim.0.snake(player);
+        let entry = LeaderboardEntry {
+            name: if humans == 1 { own_name.0.clone() } else { player_name(player, humans) },
+            score: snake.score(),
+            length: snake.length() as u32,
+            duration_millis: sim.0.elapsed_millis(),
+            timestamp,
+            seed: sim.0.seed(),
+            mode: mode.clone(),
+            difficulty: sim.0.rules().difficulty.name().to_string(),
+        };
+        inserted |= high_scores.leaderboard.insert(entry).is_some();
+    }
+
+    if !inserted {
+        return;
+    }
+
+    if let Err(err) = high_scores.leaderboard.save(&high_scores.path) {
+        warn!("Could not save leaderboard to {}: {err}", high_scores.path.display());
+    }
+}
+
+// Add the leaderboard table to a UI panel
+pub fn spawn_leaderboard(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    leaderboard: &Leaderboard,
+) {
+    let text = if leaderboard.entries.is_empty() {
+        "No scores yet".to_string()
+    } else {
+        leaderboard
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(rank, entry)| {
+                format!(
+                    "{:>2}. {:<12} {:>4}   length {:>3}   {:>5}   {}   {} {}",
+                    rank + 1,
+                    entry.name,
+                    entry.score,
+                    entry.length,
+                    entry.duration(),
+                    entry.date(),
+                    entry.difficulty,
+                    entry.mode,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    parent.spawn((
+        Text::new("Leaderboard"),
+        TextFont {
+            font: font.clone(),
+            font_size: 22.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+
+    parent.spawn((
+        Text::new(text),
+        TextFont {
+            font: font.clone(),
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.8, 0.8, 0.8)),
+    ));
+}
@@ -1,37 +1,114 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
 use crate::{
-    components::{Position, Direction, SnakeHead},
-    resources::{MovementTimer, NextHeadPosition, DirectionLocked},
+    components::{SnakeHead, Direction, DirectionQueue},
+    replay::ReplayInput,
+    resources::{MovementTimer, TickOutcome, ReplayRecorder, Sim},
+    sim::Snake,
 };
 
-// Move the snake once every timer tick
+// Move the snakes once every timer tick
 pub fn snake_movement(
     time: Res<Time>,
     mut timer: ResMut<MovementTimer>,
-    mut dir_lock: ResMut<DirectionLocked>,
-    heads: Query<(&Position, &SnakeHead)>,
-    mut next_writer: MessageWriter<NextHeadPosition>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut sim: ResMut<Sim>,
+    mut heads: Query<(&mut SnakeHead, &mut DirectionQueue)>,
+    mut outcome_writer: MessageWriter<TickOutcome>,
 ) {
     // Only move on a tick
     if !timer.0.tick(time.delta()).just_finished() {
         return;
     }
 
-    // Unlock input for this tick
-    dir_lock.0 = false;
+    let mut directions: Vec<Direction> = sim.0.snakes().iter().map(Snake::direction).collect();
+
+    for (mut head, mut queue) in &mut heads {
+        // Apply the next queued turn
+        if let Some(direction) = queue.0.pop_front() {
+            head.direction = direction;
+
+            // Record the change for the replay
+            recorder.0.push(ReplayInput { tick: sim.0.ticks(), player: head.player, direction });
+        }
+
+        if let Some(direction) = directions.get_mut(head.player) {
+            *direction = head.direction;
+        }
+    }
 
-    // Get head position + direction
-    let (head_pos, head) = heads.single().expect("Snakehead not found");
+    // Advance the simulation and send the outcome to the other systems
+    let report = sim.0.tick(&directions);
+    outcome_writer.write(TickOutcome(report));
 
-    // Compute next head position
-    let next = match head.direction {
-        Direction::Left  => Position { x: head_pos.x - 1, y: head_pos.y },
-        Direction::Right => Position { x: head_pos.x + 1, y: head_pos.y },
-        Direction::Up    => Position { x: head_pos.x,     y: head_pos.y + 1 },
-        Direction::Down  => Position { x: head_pos.x,     y: head_pos.y - 1 },
+    // Speed up as the snakes grow
+    let tick_duration = Duration::from_millis(sim.0.tick_millis() as u64);
+    if timer.0.duration() != tick_duration {
+        timer.0.set_duration(tick_duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::Position,
+        sim::{GridConfig, Rules, SnakeSim},
     };
 
-    // Send a NextHeadPosition Event to check for collisions
-    next_writer.write(NextHeadPosition(next));
-}
\ No newline at end of file
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(MovementTimer::from_millis(100))
+            .init_resource::<ReplayRecorder>()
+            .insert_resource(Sim(SnakeSim::new(GridConfig::default(), Rules::default(), 1)))
+            .add_message::<TickOutcome>()
+            .add_systems(Update, snake_movement);
+        app.world_mut().spawn((
+            SnakeHead { direction: Direction::Up, player: 0 },
+            DirectionQueue::default(),
+        ));
+        app
+    }
+
+    // Let one tick of the movement timer pass
+    fn tick(app: &mut App) {
+        let duration = app.world().resource::<MovementTimer>().0.duration();
+        app.world_mut().resource_mut::<Time>().advance_by(duration);
+        app.update();
+    }
+
+    fn queue(app: &mut App) -> Mut<'_, DirectionQueue> {
+        let mut query = app.world_mut().query::<&mut DirectionQueue>();
+        query.single_mut(app.world_mut()).unwrap()
+    }
+
+    #[test]
+    fn each_tick_applies_one_queued_turn() {
+        let mut app = app();
+        let mut queued = queue(&mut app);
+        assert!(queued.push(Direction::Left, Direction::Up));
+        assert!(queued.push(Direction::Down, Direction::Up));
+
+        // Nothing happens between ticks
+        app.update();
+        assert_eq!(queue(&mut app).0.len(), 2);
+        assert_eq!(app.world().resource::<Sim>().0.ticks(), 0);
+
+        tick(&mut app);
+        assert_eq!(queue(&mut app).0, [Direction::Down]);
+        assert_eq!(app.world().resource::<Sim>().0.snake(0).direction(), Direction::Left);
+
+        tick(&mut app);
+        assert!(queue(&mut app).0.is_empty());
+        let sim = &app.world().resource::<Sim>().0;
+        assert_eq!(sim.snake(0).direction(), Direction::Down);
+        assert_eq!(sim.snake(0).head(), Position { x: 2, y: 2 });
+
+        // Both turns are recorded for the replay on the tick they were applied
+        let recorded: Vec<_> = app.world().resource::<ReplayRecorder>().0.iter().map(|input| (input.tick, input.direction)).collect();
+        assert_eq!(recorded, [(0, Direction::Left), (1, Direction::Down)]);
+    }
+}
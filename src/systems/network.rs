@@ -0,0 +1,265 @@
+use std::time::Instant;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    components::{Food, GameEntityFilter, SnakeHead},
+    net::{ClientStatus, Snapshot},
+    resources::{GameSettings, GameState, Network, SnakeSegments},
+    settings::{Action, SteeringMode},
+    sim::{BoardMode, FoodItem},
+    systems::{
+        rendering::{spawn_borders, spawn_walls},
+        spawn::{spawn_food, spawn_segment},
+        state::spawn_head,
+    },
+};
+
+#[derive(Component)]
+pub struct OnlineUI;
+
+// Assets needed to spawn the entities of a snapshot
+#[derive(SystemParam)]
+pub struct SceneAssets<'w> {
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<ColorMaterial>>,
+    asset_server: Res<'w, AssetServer>,
+}
+
+// Snapshot the entities on screen show
+#[derive(Default)]
+pub struct Synced {
+    round: Option<u32>,
+    tick: u32,
+    food: Vec<FoodItem>,
+}
+
+// Games on a server or against a peer
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Online), setup_online)
+            .add_systems(OnExit(GameState::Online), cleanup_online)
+            .add_systems(Update, (
+                (network_input, network_update, sync_snapshot).chain(),
+                update_online_ui.after(network_update),
+                online_controls,
+            ).run_if(in_state(GameState::Online)));
+    }
+}
+
+// Build the status line of an online game
+pub fn setup_online(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut segments: ResMut<SnakeSegments>,
+    game_entities: Query<Entity, GameEntityFilter>,
+) {
+    let font = asset_server.load("fonts/FiraSans.ttf");
+
+    // Leftovers of a local game
+    for entity in &game_entities {
+        commands.entity(entity).despawn();
+    }
+    segments.0.clear();
+
+    commands.spawn((
+        OnlineUI,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        Text::new(""),
+        TextFont {
+            font,
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+// Leave the server and clear the board
+pub fn cleanup_online(
+    mut commands: Commands,
+    mut network: ResMut<Network>,
+    mut segments: ResMut<SnakeSegments>,
+    roots: Query<Entity, Or<(With<OnlineUI>, GameEntityFilter)>>,
+) {
+    if let Err(err) = network.0.leave() {
+        warn!("Could not tell {} that we left: {err}", network.0.remote());
+    }
+    commands.remove_resource::<Network>();
+
+    for entity in &roots {
+        commands.entity(entity).despawn();
+    }
+    segments.0.clear();
+}
+
+// Exchange packets with the server
+pub fn network_update(mut network: ResMut<Network>) {
+    if let Err(err) = network.0.update(Instant::now()) {
+        warn!("Network error: {err}");
+    }
+}
+
+// Send the turns of the player, both key slots steer the own snake
+pub fn network_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    mut network: ResMut<Network>,
+) {
+    for &key in keyboard_input.get_just_pressed() {
+        for (action, _) in settings.settings.key_map.slot_actions(key) {
+            let Some(heading) = network.0.heading() else {
+                return;
+            };
+            let direction = match settings.settings.steering {
+                SteeringMode::Absolute => action.direction(),
+                SteeringMode::Relative => action.rotate(heading),
+            };
+            let Some(direction) = direction else {
+                continue;
+            };
+
+            if let Err(err) = network.0.steer(direction, Instant::now()) {
+                warn!("Could not send a turn: {err}");
+            }
+        }
+    }
+}
+
+// Go back to the menu with the pause key
+pub fn online_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if settings.settings.key_map.just_pressed(Action::Pause, &keyboard_input) {
+        next_state.set(GameState::Menu);
+    }
+}
+
+// Move the entities on screen to the latest snapshot
+pub fn sync_snapshot(
+    mut commands: Commands,
+    network: Res<Network>,
+    mut assets: SceneAssets,
+    mut segments: ResMut<SnakeSegments>,
+    mut synced: Local<Synced>,
+    game_entities: Query<Entity, GameEntityFilter>,
+    foods: Query<Entity, With<Food>>,
+) {
+    let Some(snapshot) = network.0.snapshot() else {
+        return;
+    };
+    let SceneAssets { meshes, materials, asset_server } = &mut assets;
+
+    // Every round starts on a fresh board
+    if synced.round != Some(snapshot.round) {
+        for entity in &game_entities {
+            commands.entity(entity).despawn();
+        }
+        segments.0.clear();
+        *synced = Synced {
+            round: Some(snapshot.round),
+            ..default()
+        };
+
+        if snapshot.rules.board == BoardMode::Walled {
+            spawn_borders(&mut commands, meshes, materials, &snapshot.grid);
+        }
+        spawn_walls(&mut commands, meshes, materials, &snapshot.walls);
+    } else if synced.tick == snapshot.tick {
+        return;
+    }
+    synced.tick = snapshot.tick;
+
+    for (player, snake) in snapshot.snakes.iter().enumerate() {
+        if segments.0.len() <= player {
+            segments.0.push(Vec::new());
+        }
+        let entities = &mut segments.0[player];
+
+        // Reuse the entities of the last tick, the snake grows and shrinks at the end
+        for (index, &position) in snake.body.iter().enumerate() {
+            match entities.get(index) {
+                Some(&entity) => {
+                    commands.entity(entity).insert(position);
+                }
+                None if index == 0 => entities.push(spawn_head(&mut commands, meshes, materials, position, snake.direction, player)),
+                None => entities.push(spawn_segment(&mut commands, meshes, materials, position, player)),
+            }
+        }
+        for entity in entities.drain(snake.body.len()..) {
+            commands.entity(entity).despawn();
+        }
+        if let Some(&head) = entities.first() {
+            commands.entity(head).insert(SnakeHead { direction: snake.direction, player });
+        }
+    }
+
+    // Food changes rarely, spawn all of it again when it does
+    if synced.food != snapshot.food {
+        for entity in &foods {
+            commands.entity(entity).despawn();
+        }
+        for &food in &snapshot.food {
+            spawn_food(&mut commands, asset_server, food);
+        }
+        synced.food = snapshot.food.clone();
+    }
+}
+
+// Show the connection, the lobby or the scores
+pub fn update_online_ui(network: Res<Network>, mut query: Query<&mut Text, With<OnlineUI>>) {
+    let Ok(mut text) = query.single_mut() else {
+        return;
+    };
+    let remote = network.0.remote();
+    let snapshot = network.0.snapshot();
+
+    let status = match (network.0.status(Instant::now()), snapshot) {
+        (ClientStatus::Rejected(reason), _) => format!("{remote} turned us away: {reason}"),
+        (ClientStatus::TimedOut, _) => format!("Lost the connection to {remote}"),
+        (ClientStatus::PeerLeft, _) => format!("{remote} left the game"),
+        (ClientStatus::Desynced(tick), _) => format!("Out of sync with {remote} since tick {tick}"),
+        (ClientStatus::Connecting, _) | (_, None) => format!("Connecting to {remote}..."),
+        (ClientStatus::Waiting, Some(snapshot)) if snapshot.snakes.is_empty() => {
+            format!("Waiting for players: {}", snapshot.lobby.join(", "))
+        }
+        (ClientStatus::Waiting, Some(snapshot)) if snapshot.over => {
+            format!("{}   Next round soon", round_result(snapshot))
+        }
+        (ClientStatus::Waiting, Some(snapshot)) => {
+            format!("{}   You join the next round", scores_label(snapshot))
+        }
+        (ClientStatus::Playing(_), Some(snapshot)) => scores_label(snapshot),
+    };
+
+    let status = format!("{status}   Esc to leave");
+    if text.0 != status {
+        text.0 = status;
+    }
+}
+
+fn scores_label(snapshot: &Snapshot) -> String {
+    let scores: Vec<_> = snapshot
+        .snakes
+        .iter()
+        .map(|snake| format!("{}: {}", snake.name, snake.score))
+        .collect();
+    scores.join("   ")
+}
+
+fn round_result(snapshot: &Snapshot) -> String {
+    match snapshot.winner.and_then(|winner| snapshot.snakes.get(winner)) {
+        Some(snake) => format!("{} wins!", snake.name),
+        None if snapshot.snakes.len() > 1 => "Draw!".to_string(),
+        None => "Game Over".to_string(),
+    }
+}
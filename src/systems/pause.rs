@@ -0,0 +1,244 @@
+use bevy::{prelude::*, window::WindowFocused};
+
+use crate::{
+    resources::{GameSettings, GameState, ResumeCountdown},
+    settings::Action,
+    systems::{movement::snake_movement, state::MenuButtonAction},
+};
+
+const COUNTDOWN_SECS: f32 = 3.0;
+
+#[derive(Component)]
+pub struct PauseUI;
+
+#[derive(Component)]
+pub struct CountdownUI;
+
+#[derive(Component)]
+pub struct CountdownText;
+
+// Pause menu and the countdown before the game goes on
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Paused), setup_pause_menu)
+            .add_systems(OnExit(GameState::Paused), cleanup_pause_menu)
+            // Coming back from the pause counts down instead of starting a new game
+            .add_systems(OnEnter(GameState::Playing), setup_countdown.run_if(counting_down))
+            .add_systems(OnExit(GameState::Playing), cleanup_countdown)
+            .add_systems(Update, (
+                pause_controls.run_if(in_state(GameState::Playing).or(in_state(GameState::Paused))).before(snake_movement),
+                pause_on_focus_loss.run_if(in_state(GameState::Playing)),
+                update_countdown.run_if(in_state(GameState::Playing)),
+            ));
+    }
+}
+
+// Start the countdown that runs before the game continues
+pub fn resume_game(
+    next_state: &mut NextState<GameState>,
+    countdown: &mut ResumeCountdown,
+) {
+    countdown.0 = Some(Timer::from_seconds(COUNTDOWN_SECS, TimerMode::Once));
+    next_state.set(GameState::Playing);
+}
+
+// Toggle the pause with the pause keys or start/select on a gamepad
+pub fn pause_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    gamepads: Query<&Gamepad>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut countdown: ResMut<ResumeCountdown>,
+) {
+    let gamepad_pressed = gamepads
+        .iter()
+        .any(|gamepad| gamepad.any_just_pressed([GamepadButton::Start, GamepadButton::Select]));
+
+    if !settings.settings.key_map.just_pressed(Action::Pause, &keyboard_input) && !gamepad_pressed {
+        return;
+    }
+
+    match state.get() {
+        GameState::Playing => {
+            countdown.0 = None;
+            next_state.set(GameState::Paused);
+        }
+        GameState::Paused => resume_game(&mut next_state, &mut countdown),
+        _ => {}
+    }
+}
+
+// Pause when the window loses focus
+pub fn pause_on_focus_loss(
+    mut reader: MessageReader<WindowFocused>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut countdown: ResMut<ResumeCountdown>,
+) {
+    if reader.read().any(|event| !event.focused) {
+        countdown.0 = None;
+        next_state.set(GameState::Paused);
+    }
+}
+
+// Build the pause overlay
+pub fn setup_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("fonts/FiraSans.ttf");
+
+    commands
+        .spawn((
+            PauseUI,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        row_gap: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                ))
+                .with_children(|panel| {
+                    // Title
+                    panel.spawn((
+                        Text::new("Paused"),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 40.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                    ));
+
+                    let buttons = [
+                        (MenuButtonAction::Resume, "Resume"),
+                        (MenuButtonAction::Restart, "Restart"),
+                        (MenuButtonAction::QuitToMenu, "Quit to menu"),
+                    ];
+
+                    for (action, label) in buttons {
+                        panel.spawn((
+                            Button,
+                            action,
+                            Node {
+                                width: Val::Px(200.0),
+                                padding: UiRect::all(Val::Px(12.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    Text::new(label),
+                                    TextFont {
+                                        font: font.clone(),
+                                        font_size: 24.0,
+                                        ..default()
+                                    },
+                                    TextColor(Color::WHITE),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+// Remove the pause overlay
+pub fn cleanup_pause_menu(
+    mut commands: Commands,
+    roots: Query<Entity, With<PauseUI>>,
+) {
+    for entity in &roots {
+        commands.entity(entity).despawn();
+    }
+}
+
+// Run condition while the resume countdown is running
+pub fn counting_down(countdown: Res<ResumeCountdown>) -> bool {
+    countdown.0.is_some()
+}
+
+// Show the countdown in the middle of the board
+pub fn setup_countdown(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    let font = asset_server.load("fonts/FiraSans.ttf");
+
+    commands
+        .spawn((
+            CountdownUI,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                CountdownText,
+                Text::new(format!("{}", COUNTDOWN_SECS as u32)),
+                TextFont {
+                    font,
+                    font_size: 96.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        });
+}
+
+// Count down to zero and let the snake move again
+pub fn update_countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut countdown: ResMut<ResumeCountdown>,
+    roots: Query<Entity, With<CountdownUI>>,
+    mut texts: Query<&mut Text, With<CountdownText>>,
+) {
+    let Some(timer) = &mut countdown.0 else {
+        return;
+    };
+
+    if timer.tick(time.delta()).is_finished() {
+        countdown.0 = None;
+        for entity in &roots {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let remaining = timer.remaining_secs().ceil() as u32;
+    for mut text in &mut texts {
+        text.0 = remaining.to_string();
+    }
+}
+
+// Remove the countdown when the game is left before it finished
+pub fn cleanup_countdown(
+    mut commands: Commands,
+    roots: Query<Entity, With<CountdownUI>>,
+) {
+    for entity in &roots {
+        commands.entity(entity).despawn();
+    }
+}
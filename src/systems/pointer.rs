@@ -0,0 +1,77 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    components::{AiSnake, SnakeHead, Direction, DirectionQueue},
+    resources::GameSettings,
+    settings::SteeringMode,
+};
+
+// Shortest drag in pixels that counts as a swipe, shorter ones are clicks
+const SWIPE_MIN_DISTANCE: f32 = 30.0;
+
+// Steer with mouse or touch swipes, or by clicking next to the head
+pub fn pointer_input(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    settings: Res<GameSettings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut heads: Query<(&SnakeHead, &Transform, &mut DirectionQueue), Without<AiSnake>>,
+    mut drag_start: Local<Option<Vec2>>,
+) {
+    // The pointer always steers the first player
+    let Some((head, head_transform, mut queue)) = heads.iter_mut().find(|(head, _, _)| head.player == 0) else {
+        return;
+    };
+
+    // Finished gestures as start and end in window coordinates
+    let mut gestures: Vec<(Vec2, Vec2)> = touches
+        .iter_just_released()
+        .map(|touch| (touch.start_position(), touch.position()))
+        .collect();
+
+    // Relative steering uses the mouse buttons to rotate
+    let cursor = windows.single().ok().and_then(Window::cursor_position);
+    if settings.settings.steering == SteeringMode::Absolute
+        && let Some(cursor) = cursor
+    {
+        if mouse_input.just_pressed(MouseButton::Left) {
+            *drag_start = Some(cursor);
+        }
+        // Drop drags that were released while the game wasn't running
+        if !mouse_input.pressed(MouseButton::Left) && !mouse_input.just_released(MouseButton::Left) {
+            *drag_start = None;
+        }
+        if mouse_input.just_released(MouseButton::Left)
+            && let Some(start) = drag_start.take()
+        {
+            gestures.push((start, cursor));
+        }
+    }
+
+    for (start, end) in gestures {
+        let swipe = end - start;
+
+        let new_dir = if swipe.length() >= SWIPE_MIN_DISTANCE {
+            // Window coordinates point down
+            Direction::dominant(Vec2::new(swipe.x, -swipe.y))
+        } else {
+            // Click on the side of the head to go that way
+            let Some(target) = cameras
+                .iter()
+                .find_map(|(camera, transform)| camera.viewport_to_world_2d(transform, end).ok())
+            else {
+                continue;
+            };
+            let offset = target - head_transform.translation.truncate();
+
+            // Clicks on the head itself have no side
+            if offset.abs().max_element() < head_transform.scale.x / 2.0 {
+                continue;
+            }
+            Direction::dominant(offset)
+        };
+
+        queue.push(new_dir, head.direction);
+    }
+}
@@ -1,28 +1,60 @@
 use bevy::{
+    ecs::system::SystemParam,
     prelude::*,
     window::PrimaryWindow
 };
 
-use crate::components::{Size, Position, Border};
+use crate::{
+    components::{Size, Position, Border},
+    resources::{GameState, LevelEditor, Network, Sim},
+    sim::GridConfig,
+};
 
-const GRID_WIDTH: i32 = 15;
-const GRID_HEIGHT: i32 = 15;
 const BORDER_COLOR: bevy::prelude::Color = Color::srgb(35.0/255.0, 71.0/255.0, 125.0/255.0);
 
 
 
+// Grid of the online or running game, the level in the editor or the one the next game will use
+#[derive(SystemParam)]
+pub struct ActiveGrid<'w> {
+    state: Res<'w, State<GameState>>,
+    network: Option<Res<'w, Network>>,
+    editor: Option<Res<'w, LevelEditor>>,
+    sim: Option<Res<'w, Sim>>,
+    grid_config: Res<'w, GridConfig>,
+}
+
+impl ActiveGrid<'_> {
+    pub fn get(&self) -> GridConfig {
+        if let Some(snapshot) = self.network.as_ref().and_then(|network| network.0.snapshot()) {
+            return snapshot.grid;
+        }
+        if *self.state.get() == GameState::Editor
+            && let Some(editor) = &self.editor
+        {
+            return editor.level.grid;
+        }
+        self.sim.as_ref().map_or(*self.grid_config, |sim| *sim.0.grid())
+    }
+}
+
+// Size of a single tile so the grid fits into the window
+fn tile_size(window: &Window, grid: &GridConfig) -> f32 {
+    let padding = 0.85;
+
+    let tile_x = window.width() * padding / grid.width as f32;
+    let tile_y = window.height() * padding / grid.height as f32;
+    tile_x.min(tile_y)
+}
+
 // Scale sprites based on the tile size so the grid fits into the window
 pub fn size_scaling(
     windows: Query<&Window, With<PrimaryWindow>>,
+    grid: ActiveGrid,
     mut q: Query<(&Size, &mut Transform)>,
 ) {
     let window = windows.single().expect("no primary window");
-
-    let padding = 0.85;
-        
-    let tile_x = window.width() * padding / GRID_WIDTH as f32;
-    let tile_y = window.height() * padding / GRID_HEIGHT as f32;
-    let tile = tile_x.min(tile_y);
+    let tile = tile_size(window, &grid.get());
 
     for (size, mut transform) in &mut q {
         transform.scale = Vec3::new(
@@ -36,25 +68,31 @@ pub fn size_scaling(
 // Convert grid coordinates into the screen coordinates
 pub fn position_translation(
     windows: Query<&Window, With<PrimaryWindow>>,
+    grid: ActiveGrid,
     mut q: Query<(&Position, &mut Transform)>,
 ) {
     let window = windows.single().expect("no primary window");
-
-    let padding = 0.85;
-
-    let tile_x = window.width() * padding / GRID_WIDTH as f32;
-    let tile_y = window.height() * padding / GRID_HEIGHT as f32;
-    let tile = tile_x.min(tile_y);
+    let grid = grid.get();
+    let tile = tile_size(window, &grid);
 
     for (pos, mut transform) in &mut q {
         transform.translation = Vec3::new(
-            (pos.x as f32 + 0.5 - GRID_WIDTH as f32 / 2.0) * tile,
-            (pos.y as f32 + 0.5 - GRID_HEIGHT as f32 / 2.0) * tile,
+            (pos.x as f32 + 0.5 - grid.width as f32 / 2.0) * tile,
+            (pos.y as f32 + 0.5 - grid.height as f32 / 2.0) * tile,
             0.0,
         );
     }
 }
 
+// Tile under a point in world coordinates, None off the grid
+pub fn tile_at(window: &Window, grid: &GridConfig, point: Vec2) -> Option<Position> {
+    let tile = tile_size(window, grid);
+    let pos = Position {
+        x: (point.x / tile + grid.width as f32 / 2.0).floor() as i32,
+        y: (point.y / tile + grid.height as f32 / 2.0).floor() as i32,
+    };
+    grid.contains(pos).then_some(pos)
+}
 
 // Spawn a 2d camera
 pub fn setup_camera(
@@ -64,29 +102,24 @@ pub fn setup_camera(
 }
 
 
-// Spawn the borders of the grid
+// Spawn the borders around the grid
 pub fn spawn_borders(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    border_query: Query<Entity, With<Border>>,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    grid: &GridConfig,
 ) {
-    // Despawn old borders
-    for entity in border_query.iter() {
-        commands.entity(entity).despawn();
-    }
-
     // Spawn visible borders
     let mesh = meshes.add(Rectangle::new(1.0, 1.0));
     let material = materials.add(BORDER_COLOR);
     
     // Horizontal borders 
-    for x in -1..GRID_WIDTH + 1 {
+    for x in -1..grid.width + 1 {
         commands.spawn((
             Mesh2d(mesh.clone()),
             MeshMaterial2d(material.clone()),
             Border,
-            Position { x: x as i32, y: -1 },
+            Position { x, y: -1 },
             Size::square(1.0),
             Transform::default(),
             GlobalTransform::default(),
@@ -96,7 +129,7 @@ pub fn spawn_borders(
             Mesh2d(mesh.clone()),
             MeshMaterial2d(material.clone()),
             Border,
-            Position { x: x as i32, y: GRID_HEIGHT as i32 },
+            Position { x, y: grid.height },
             Size::square(1.0),
             Transform::default(),
             GlobalTransform::default(),
@@ -104,12 +137,12 @@ pub fn spawn_borders(
     }
 
     // Vertical borders
-    for y in -1..GRID_HEIGHT + 1 {
+    for y in -1..grid.height + 1 {
         commands.spawn((
             Mesh2d(mesh.clone()),
             MeshMaterial2d(material.clone()),
             Border,
-            Position { x: -1, y: y as i32 },
+            Position { x: -1, y },
             Size::square(1.0),
             Transform::default(),
             GlobalTransform::default(),
@@ -119,7 +152,7 @@ pub fn spawn_borders(
             Mesh2d(mesh.clone()),
             MeshMaterial2d(material.clone()),
             Border,
-            Position { x: GRID_WIDTH as i32, y: y as i32 },
+            Position { x: grid.width, y },
             Size::square(1.0),
             Transform::default(),
             GlobalTransform::default(),
@@ -127,3 +160,25 @@ pub fn spawn_borders(
     }    
 }
 
+// Spawn the walls of a level, they block the snake like the borders
+pub fn spawn_walls<'a>(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    walls: impl IntoIterator<Item = &'a Position>,
+) {
+    let mesh = meshes.add(Rectangle::new(1.0, 1.0));
+    let material = materials.add(BORDER_COLOR);
+
+    for &pos in walls {
+        commands.spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            Border,
+            pos,
+            Size::square(1.0),
+            Transform::default(),
+            GlobalTransform::default(),
+        ));
+    }
+}
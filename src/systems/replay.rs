@@ -0,0 +1,316 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use crate::{
+    components::{SnakeHead, GameEntityFilter},
+    replay::{Playback, Replay},
+    resources::{
+        CurrentReplay, GameOverEvent, GameState, MovementTimer, ReplayRecorder, Score, SeekReplay,
+        Sim, SnakeSegments,
+    },
+    systems::{
+        collision::snake_collision,
+        movement::snake_movement,
+        state::{cleanup_score_ui, setup_score_ui, spawn_game},
+    },
+};
+
+const SCRUB_TICKS: u32 = 25;
+const PLAYBACK_SPEEDS: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+
+#[derive(Component)]
+pub struct ReplayUI;
+
+#[derive(Component)]
+pub struct ReplayStatusText;
+
+#[derive(Component)]
+pub struct ReplayProgressBar;
+
+#[derive(Component)]
+pub struct ReplayProgressFill;
+
+// Saves every finished game and plays replays back
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<SeekReplay>()
+            .add_systems(OnEnter(GameState::Replay), (setup_replay, setup_score_ui))
+            .add_systems(OnExit(GameState::Replay), (cleanup_replay, cleanup_score_ui))
+            .add_systems(Update, save_replay.run_if(in_state(GameState::Playing)).after(snake_collision))
+            .add_systems(Update, (
+                (replay_controls, seek_replay, replay_input).chain().before(snake_movement),
+                replay_finished.after(snake_collision),
+                update_replay_ui.after(seek_replay),
+            ).run_if(in_state(GameState::Replay)));
+    }
+}
+
+// Write the replay of the finished game to disk
+pub fn save_replay(
+    mut reader: MessageReader<GameOverEvent>,
+    sim: Res<Sim>,
+    recorder: Res<ReplayRecorder>,
+    mut current_replay: ResMut<CurrentReplay>,
+) {
+    if reader.is_empty() {
+        return;
+    }
+    reader.clear();
+
+    let replay = Replay::record(&sim.0, recorder.0.clone());
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let path = Replay::default_dir().join(format!("replay-{timestamp}-{}.ron", replay.seed));
+
+    match replay.save(&path) {
+        Ok(()) => info!("Saved replay to {}", path.display()),
+        Err(err) => warn!("Could not save replay to {}: {err}", path.display()),
+    }
+
+    current_replay.0 = Some(Playback::new(replay));
+}
+
+// Build the playback controls and start the replay from the beginning
+pub fn setup_replay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut time: ResMut<Time<Virtual>>,
+    mut seek_writer: MessageWriter<SeekReplay>,
+) {
+    let font = asset_server.load("fonts/FiraSans.ttf");
+
+    time.unpause();
+    time.set_relative_speed(1.0);
+    seek_writer.write(SeekReplay(0));
+
+    commands
+        .spawn((
+            ReplayUI,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                left: Val::Percent(10.0),
+                width: Val::Percent(80.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            // Status and key help
+            parent.spawn((
+                ReplayStatusText,
+                Text::new(""),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+
+            // Progress bar, click or drag to scrub
+            parent
+                .spawn((
+                    ReplayProgressBar,
+                    Button,
+                    RelativeCursorPosition::default(),
+                    Node {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.2)),
+                ))
+                .with_children(|bar| {
+                    bar.spawn((
+                        ReplayProgressFill,
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.0, 0.5, 0.0)),
+                    ));
+                });
+        });
+}
+
+// Remove the playback controls and restore the game speed
+pub fn cleanup_replay(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    roots: Query<Entity, With<ReplayUI>>,
+) {
+    time.unpause();
+    time.set_relative_speed(1.0);
+
+    for entity in &roots {
+        commands.entity(entity).despawn();
+    }
+}
+
+// Pause, step, fast-forward and scrub through the replay
+pub fn replay_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut seek_writer: MessageWriter<SeekReplay>,
+    current_replay: Res<CurrentReplay>,
+    sim: Option<Res<Sim>>,
+    progress_bar: Query<(&Interaction, &RelativeCursorPosition), With<ReplayProgressBar>>,
+) {
+    let (Some(playback), Some(sim)) = (&current_replay.0, sim) else {
+        return;
+    };
+    let replay = playback.replay();
+    let tick = sim.0.ticks();
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+
+    // Cycle through the playback speeds
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        let speed = time.relative_speed();
+        let next = PLAYBACK_SPEEDS
+            .iter()
+            .copied()
+            .find(|&s| s > speed)
+            .unwrap_or(PLAYBACK_SPEEDS[0]);
+        time.set_relative_speed(next);
+    }
+
+    // Step a single tick or scrub further with shift held
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let stride = if shift { SCRUB_TICKS } else { 1 };
+
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        time.pause();
+        seek_writer.write(SeekReplay((tick + stride).min(replay.ticks)));
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        time.pause();
+        seek_writer.write(SeekReplay(tick.saturating_sub(stride)));
+    } else if keyboard_input.just_pressed(KeyCode::Home) {
+        seek_writer.write(SeekReplay(0));
+    }
+
+    // Scrub with the progress bar
+    for (interaction, cursor) in &progress_bar {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(normalized) = cursor.normalized {
+            let fraction = (normalized.x + 0.5).clamp(0.0, 1.0);
+            let target = (fraction * replay.ticks as f32).round() as u32;
+            if target != tick {
+                seek_writer.write(SeekReplay(target));
+            }
+        }
+    }
+}
+
+// Re-simulate the replay up to the requested tick and rebuild the board
+pub fn seek_replay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut seek_reader: MessageReader<SeekReplay>,
+    current_replay: Res<CurrentReplay>,
+    mut segments: ResMut<SnakeSegments>,
+    mut score: ResMut<Score>,
+    mut timer: ResMut<MovementTimer>,
+    game_entities: Query<Entity, GameEntityFilter>,
+) {
+    // Only the latest seek of this frame matters
+    let Some(SeekReplay(tick)) = seek_reader.read().last() else {
+        return;
+    };
+    let Some(playback) = &current_replay.0 else {
+        return;
+    };
+
+    for entity in game_entities.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let sim = playback.simulate_to(*tick);
+    spawn_game(&mut commands, &mut meshes, &mut materials, &asset_server, &mut segments, &sim);
+
+    score.0 = sim.snakes().iter().map(|snake| snake.score()).collect();
+    *timer = MovementTimer::from_millis(sim.tick_millis());
+    commands.insert_resource(Sim(sim));
+}
+
+// Steer the snakes with the recorded direction changes
+pub fn replay_input(
+    current_replay: Res<CurrentReplay>,
+    sim: Res<Sim>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    let Some(playback) = &current_replay.0 else {
+        return;
+    };
+
+    for mut head in &mut heads {
+        if let Some(direction) = playback.direction_at(head.player, sim.0.ticks()) {
+            head.direction = direction;
+        }
+    }
+}
+
+// Hold the last frame once the recorded game is over
+pub fn replay_finished(
+    mut reader: MessageReader<GameOverEvent>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if !reader.is_empty() {
+        reader.clear();
+        time.pause();
+    }
+}
+
+// Show the playback position
+pub fn update_replay_ui(
+    current_replay: Res<CurrentReplay>,
+    sim: Res<Sim>,
+    time: Res<Time<Virtual>>,
+    mut status: Query<&mut Text, With<ReplayStatusText>>,
+    mut fill: Query<&mut Node, With<ReplayProgressFill>>,
+) {
+    let Some(playback) = &current_replay.0 else {
+        return;
+    };
+    let replay = playback.replay();
+    let tick = sim.0.ticks();
+
+    if let Ok(mut text) = status.single_mut() {
+        let state = if time.is_paused() { "paused" } else { "playing" };
+        text.0 = format!(
+            "Replay  tick {tick}/{}  {state}  x{}\n[Space] pause  [Left/Right] step  [Shift] scrub  [F] speed  [Home] restart  [Esc] menu",
+            replay.ticks,
+            time.relative_speed(),
+        );
+    }
+
+    if let Ok(mut node) = fill.single_mut() {
+        let progress = tick as f32 / replay.ticks.max(1) as f32;
+        node.width = Val::Percent(progress * 100.0);
+    }
+}
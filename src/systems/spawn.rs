@@ -1,27 +1,55 @@
 use bevy::prelude::*;
-use std::collections::HashSet;
-
-use rand::Rng;
 
 use crate::{
     components::{Food, Position, Size, SnakeSegment},
-    resources::{FoodSpawnTimer, SnakeSegments}
+    resources::TickOutcome,
+    sim::{FoodItem, MAX_PLAYERS},
 };
 
-const SNAKE_SEGMENT_COLOR: bevy::prelude::Color = Color::srgb(216.0/255.0, 219.0/255.0, 22.0/255.0);
-const GRID_WIDTH: u32 = 15;
-const GRID_HEIGHT: u32 = 15;
-
-
-// Spawn a snake segment
+const SNAKE_SEGMENT_COLORS: [bevy::prelude::Color; MAX_PLAYERS] = [
+    Color::srgb(216.0/255.0, 219.0/255.0, 22.0/255.0),
+    Color::srgb(22.0/255.0, 160.0/255.0, 219.0/255.0),
+    Color::srgb(219.0/255.0, 70.0/255.0, 22.0/255.0),
+    Color::srgb(170.0/255.0, 22.0/255.0, 219.0/255.0),
+];
+
+const FOOD_SPRITES: [&str; 23] = [
+    "food/Apple.png",
+    "food/Avocado.png",
+    "food/Bacon.png",
+    "food/Beer.png",
+    "food/Cabbage.png",
+    "food/Cheese.png",
+    "food/Cherry.png",
+    "food/ChickenLeg.png",
+    "food/Chips.png",
+    "food/Cookie.png",
+    "food/Eggplant.png",
+    "food/Eggs.png",
+    "food/Milk.png",
+    "food/Olive.png",
+    "food/Pie.png",
+    "food/Pretzel.png",
+    "food/RubberDuck.png",
+    "food/Sashimi.png",
+    "food/Shrimp.png",
+    "food/SoftDrink.png",
+    "food/Steak.png",
+    "food/Turnip.png",
+    "food/Watermelon.png",
+];
+
+
+// Spawn a snake segment in the color of its player
 pub fn spawn_segment(
     commands: &mut Commands,
     meshes: &mut Assets<Mesh>,
     materials: &mut Assets<ColorMaterial>,
     position: Position,
+    player: usize,
 ) -> Entity {
     let mesh = meshes.add(Rectangle::new(1.0, 1.0));
-    let snake_segment_color = SNAKE_SEGMENT_COLOR;
+    let snake_segment_color = SNAKE_SEGMENT_COLORS[player % MAX_PLAYERS];
 
     commands.spawn((
         Mesh2d(mesh),
@@ -35,90 +63,38 @@ pub fn spawn_segment(
     .id()
 }
 
-// Spawn food at a random grid position every x seconds
+// Spawn a food sprite, the variant of the food picks the image
+pub fn spawn_food(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    food: FoodItem,
+) -> Entity {
+    let texture = asset_server.load(FOOD_SPRITES[food.variant as usize % FOOD_SPRITES.len()]);
+
+    commands.spawn((
+        Sprite {
+        image: texture,
+        custom_size: Some(Vec2::new(1.0, 1.0)), // scaled by your translation system
+        ..default()
+        },
+        Food,
+        food.pos,
+        Size::square(1.0),
+        Transform::default(),
+        GlobalTransform::default(),
+    ))
+    .id()
+}
+
+// Spawn the food the simulation placed during a tick
 pub fn food_spawner(
-    time: Res<Time>,
     asset_server: Res<AssetServer>,
-    mut timer: ResMut<FoodSpawnTimer>,
     mut commands: Commands,
-    segments: Res<SnakeSegments>,
-    positions: Query<&Position>,
-    food_positions: Query<&Position, With<Food>>,
+    mut outcome_reader: MessageReader<TickOutcome>,
 ) {
-
-    let choices = [
-        "food/Apple.png",
-        "food/Avocado.png",
-        "food/Bacon.png",
-        "food/Beer.png",
-        "food/Cabbage.png",
-        "food/Cheese.png",
-        "food/Cherry.png",
-        "food/ChickenLeg.png",
-        "food/Chips.png",
-        "food/Cookie.png",
-        "food/Eggplant.png",
-        "food/Eggs.png",
-        "food/Milk.png",
-        "food/Olive.png",
-        "food/Pie.png",
-        "food/Pretzel.png",
-        "food/RubberDuck.png",
-        "food/Sashimi.png",
-        "food/Shrimp.png",
-        "food/SoftDrink.png",
-        "food/Steak.png",
-        "food/Turnip.png",
-        "food/Watermelon.png",
-    ];
-
-    let mut rng = rand::thread_rng();
-    let index = rng.gen_range(0..choices.len());
-
-    if timer.0.tick(time.delta()).just_finished() {
-
-        // Collect all occupied positions
-        let snake_positions: std::collections::HashSet<Position> = segments
-            .0
-            .iter()
-            .map(|&entity| *positions.get(entity).expect("Snake entity without Position"))
-            .collect();
-        
-        let food_positions_set: HashSet<Position> = food_positions.iter().cloned().collect();
-        
-        // Make a list of all empty tiles
-        let mut free_tiles: Vec<Position> = Vec::new();
-
-        for x in 0..GRID_WIDTH as i32 {
-            for y in 0..GRID_HEIGHT as i32 {
-                let pos = Position { x, y };
-                if !snake_positions.contains(&pos) && !food_positions_set.contains(&pos) {
-                    free_tiles.push(pos);
-                }
-            }
+    for TickOutcome(report) in outcome_reader.read() {
+        if let Some(food) = report.spawned_food {
+            spawn_food(&mut commands, &asset_server, food);
         }
-
-        // No free tiles
-        if free_tiles.is_empty() {
-            return;
-        }
-
-        // Pick a free tile
-        let pos = free_tiles[rng.gen_range(0..free_tiles.len())];
-        let texture = asset_server.load(choices[index]);
-
-        commands.spawn((
-            Sprite {
-            image: texture,
-            custom_size: Some(Vec2::new(1.0, 1.0)), // scaled by your translation system
-            ..default()
-            },
-            Food,
-            pos,
-            Size::square(1.0),
-            Transform::default(),
-            GlobalTransform::default(),
-        ));
     }
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+
+use crate::{
+    resources::{GameState, Network, PlayerName, Sim, Spectators},
+    spectator::Frame,
+    systems::{collision::snake_collision, network::network_update, state::player_name},
+};
+
+// Streams the board when a spectator endpoint is open
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, publish_spectators
+            .after(snake_collision)
+            .after(network_update)
+            .run_if(resource_exists::<Spectators>));
+    }
+}
+
+// Stream the board to the spectators whenever it changes
+pub fn publish_spectators(
+    mut spectators: ResMut<Spectators>,
+    state: Res<State<GameState>>,
+    sim: Option<Res<Sim>>,
+    network: Option<Res<Network>>,
+    own_name: Res<PlayerName>,
+    mut last: Local<Option<Frame>>,
+) {
+    if let Err(err) = spectators.0.update() {
+        warn!("Spectator endpoint error: {err}");
+    }
+
+    let frame = match (state.get(), &sim, &network) {
+        (GameState::Online, _, Some(network)) => network.0.snapshot().map(Frame::of_snapshot),
+        (GameState::Playing | GameState::Paused | GameState::GameOver | GameState::Replay, Some(sim), _) => {
+            let humans = sim.0.humans();
+            let names: Vec<_> = (0..sim.0.players())
+                .map(|player| match humans {
+                    1 if player == 0 => own_name.0.clone(),
+                    _ => player_name(player, humans),
+                })
+                .collect();
+
+            let mut frame = Frame::of_sim(&sim.0, &names);
+            frame.paused = *state.get() == GameState::Paused;
+            Some(frame)
+        }
+        _ => None,
+    };
+
+    // The last frame stays up while the menus are open
+    if let Some(frame) = frame
+        && last.as_ref() != Some(&frame)
+    {
+        spectators.0.publish(&frame);
+        *last = Some(frame);
+    }
+}
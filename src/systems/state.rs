@@ -1,12 +1,33 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 use crate::{
-    components::{SnakeHead, SnakeSegment, Position, Direction, Size, Food},
-    resources::{SnakeSegments, LastTailPosition, GameOverEvent, GameState, Score, HighScore},
-    systems::spawn::spawn_segment
+    components::{AiSnake, SnakeHead, Size, GameEntityFilter, DirectionQueue, Direction, Position},
+    resources::{
+        SnakeSegments, GameOverEvent, GameState, Score, HighScores, Sim, GameSeed, GameRules,
+        MovementTimer, ReplayRecorder, CurrentReplay, ResumeCountdown,
+        GameSettings, RestartGame, PlayerCount, Rivals, Autopilot, Levels,
+    },
+    agent::AgentRegistry,
+    settings::{Action, KEYS_PER_ACTION},
+    sim::{BoardMode, GridConfig, Snake, SnakeSim, MAX_PLAYERS},
+    systems::{
+        controls::save_settings,
+        leaderboard::spawn_leaderboard,
+        pause::resume_game,
+        rendering::{spawn_borders, spawn_walls},
+        spawn::{spawn_segment, spawn_food},
+    },
 };
 
-const SNAKE_HEAD_COLOR: bevy::prelude::Color = Color::srgb(193.0/255.0, 196.0/255.0, 0.0/255.0);
+const SNAKE_HEAD_COLORS: [bevy::prelude::Color; MAX_PLAYERS] = [
+    Color::srgb(193.0/255.0, 196.0/255.0, 0.0/255.0),
+    Color::srgb(0.0/255.0, 130.0/255.0, 196.0/255.0),
+    Color::srgb(196.0/255.0, 45.0/255.0, 0.0/255.0),
+    Color::srgb(140.0/255.0, 0.0/255.0, 196.0/255.0),
+];
+
+// Most players that can share the keyboard, one per key slot
+const MAX_LOCAL_PLAYERS: usize = KEYS_PER_ACTION;
 
 #[derive(Component)]
 pub struct MainMenuUI;
@@ -17,16 +38,44 @@ pub struct GameOverUI;
 #[derive(Component)]
 pub struct ScoreUI;
 
-#[derive(Component)]
+// Menu button texts that show a setting
+#[derive(Component, Clone, Copy)]
+pub enum MenuText {
+    Players,
+    Rivals,
+    AiSkill,
+    Difficulty,
+    Board,
+    Autopilot,
+    Level,
+    Seed,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
 pub enum MenuButtonAction {
     Play,
     Restart,
+    WatchReplay,
+    ToggleBoard,
+    CycleDifficulty,
+    CyclePlayers,
+    CycleRivals,
+    CycleAiSkill,
+    CycleAutopilot,
+    CycleLevel,
+    ToggleSeed,
+    Resume,
+    QuitToMenu,
+    Controls,
+    OpenEditor,
 }
 
 // Build the main menu screen
 pub fn setup_main_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    high_scores: Res<HighScores>,
+    settings: MenuSettings,
 ) {
     let font = asset_server.load("fonts/FiraSans.ttf");
 
@@ -88,10 +137,134 @@ pub fn setup_main_menu(
                                 TextColor(Color::WHITE),
                             ));
                         });
+
+                    // Buttons that cycle through the game settings
+                    for text in [MenuText::Level, MenuText::Players, MenuText::Rivals, MenuText::AiSkill, MenuText::Difficulty, MenuText::Board, MenuText::Autopilot, MenuText::Seed] {
+                        let label = menu_label(text, &settings);
+                        spawn_setting_button(parent, &font, text, label);
+                    }
+
+                    // Controls and level editor buttons
+                    for (action, label) in [(MenuButtonAction::Controls, "Controls"), (MenuButtonAction::OpenEditor, "Level editor")] {
+                        parent.spawn((
+                            Button,
+                            action,
+                            Node {
+                                margin: UiRect::bottom(Val::Px(16.0)),
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    Text::new(label),
+                                    TextFont {
+                                        font: font.clone(),
+                                        font_size: 20.0,
+                                        ..Default::default()
+                                    },
+                                    TextColor(Color::WHITE),
+                                ));
+                            });
+                    }
+
+                    spawn_leaderboard(parent, &font, &high_scores.leaderboard);
                 });
         });
 }
 
+// Button that cycles a setting and shows its current value
+fn spawn_setting_button(
+    parent: &mut ChildSpawnerCommands,
+    font: &Handle<Font>,
+    text: MenuText,
+    label: String,
+) {
+    let action = match text {
+        MenuText::Players => MenuButtonAction::CyclePlayers,
+        MenuText::Rivals => MenuButtonAction::CycleRivals,
+        MenuText::AiSkill => MenuButtonAction::CycleAiSkill,
+        MenuText::Difficulty => MenuButtonAction::CycleDifficulty,
+        MenuText::Board => MenuButtonAction::ToggleBoard,
+        MenuText::Autopilot => MenuButtonAction::CycleAutopilot,
+        MenuText::Level => MenuButtonAction::CycleLevel,
+        MenuText::Seed => MenuButtonAction::ToggleSeed,
+    };
+
+    parent.spawn((
+        Button,
+        action,
+        Node {
+            margin: UiRect::bottom(Val::Px(8.0)),
+            padding: UiRect::all(Val::Px(8.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+        ))
+        .with_children(|button| {
+            button.spawn((
+                text,
+                Text::new(label),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    ..Default::default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        });
+}
+
+// Settings the menu buttons show
+#[derive(SystemParam)]
+pub struct MenuSettings<'w> {
+    rules: Res<'w, GameRules>,
+    players: Res<'w, PlayerCount>,
+    rivals: Res<'w, Rivals>,
+    autopilot: Res<'w, Autopilot>,
+    levels: Res<'w, Levels>,
+    seed: Res<'w, GameSeed>,
+}
+
+impl MenuSettings<'_> {
+    fn is_changed(&self) -> bool {
+        self.rules.is_changed()
+            || self.players.is_changed()
+            || self.rivals.is_changed()
+            || self.autopilot.is_changed()
+            || self.levels.is_changed()
+            || self.seed.is_changed()
+    }
+}
+
+// Current value of a menu setting
+fn menu_label(text: MenuText, settings: &MenuSettings) -> String {
+    let MenuSettings { rules, players, rivals, autopilot, levels, seed } = settings;
+    match text {
+        MenuText::Players if players.0 > 1 => format!("Players: {} (versus)", players.0),
+        MenuText::Players => format!("Players: {}", players.0),
+        MenuText::Rivals => format!("Rivals: {}", rivals.count),
+        MenuText::AiSkill => format!("Rival skill: {}", rivals.skill.name()),
+        MenuText::Difficulty => format!("Difficulty: {}", rules.0.difficulty.name()),
+        MenuText::Board => format!("Board: {}", rules.0.board.name()),
+        MenuText::Autopilot if autopilot.enabled => format!("Autopilot: {}", autopilot.agent),
+        MenuText::Autopilot => "Autopilot: Off".to_string(),
+        MenuText::Level => match levels.current() {
+            Some(level) => format!("Level: {}", level.name),
+            None => "Level: Open board".to_string(),
+        },
+        MenuText::Seed => match seed.fixed {
+            Some(seed) => format!("Seed: {seed}"),
+            None => "Seed: Random".to_string(),
+        },
+    }
+}
+
 // Remove the main menu screen
 pub fn cleanup_main_menu(
     mut commands: Commands,
@@ -107,14 +280,25 @@ pub fn setup_game_over_screen(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     score: Res<Score>,
-    mut highscore: ResMut<HighScore>,
+    high_scores: Res<HighScores>,
+    seed: Res<GameSeed>,
+    replay: Res<CurrentReplay>,
+    sim: Option<Res<Sim>>,
 ) {
-    let font = asset_server.load("fonts/FiraSans.ttf");
+    // Versus games end with a winner instead of a highscore
+    let versus = sim.as_ref().filter(|sim| sim.0.players() > 1);
+    let humans = sim.as_ref().map_or(1, |sim| sim.0.humans());
+    let title = match versus {
+        Some(sim) => match sim.0.winner() {
+            Some(player) => format!("{} wins!", player_name(player, humans)),
+            None if sim.0.snakes().iter().any(Snake::is_alive) => "The rivals win!".to_string(),
+            None => "Draw!".to_string(),
+        },
+        None if sim.as_ref().is_some_and(|sim| sim.0.goal_reached()) => "Level complete!".to_string(),
+        None => "Game Over :(".to_string(),
+    };
 
-    // Update highscore
-    if score.0 > highscore.0 {
-        highscore.0 = score.0;
-    }
+    let font = asset_server.load("fonts/FiraSans.ttf");
 
     commands
         .spawn((
@@ -143,7 +327,7 @@ pub fn setup_game_over_screen(
                 .with_children(|panel| {
                     // Title
                     panel.spawn((
-                        Text::new("Game Over :("),
+                        Text::new(title),
                         TextFont {
                             font: font.clone(),
                             font_size: 40.0,
@@ -154,7 +338,7 @@ pub fn setup_game_over_screen(
 
                     // Score
                     panel.spawn((
-                        Text::new(format!("Score: {}", score.0)),
+                        Text::new(scores_label(&score.0, humans)),
                         TextFont {
                             font: font.clone(),
                             font_size: 28.0,
@@ -163,15 +347,30 @@ pub fn setup_game_over_screen(
                         TextColor(Color::WHITE),
                     ));
 
-                    // Highscore
+                    if versus.is_none() {
+                        // Highscore
+                        panel.spawn((
+                            Text::new(format!("Highscore: {}", high_scores.leaderboard.best().map_or(0, |entry| entry.score))),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 24.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                        ));
+
+                        spawn_leaderboard(panel, &font, &high_scores.leaderboard);
+                    }
+
+                    // Seed to reproduce the game
                     panel.spawn((
-                        Text::new(format!("Highscore: {}", highscore.0)),
+                        Text::new(format!("Seed: {}", seed.current)),
                         TextFont {
                             font: font.clone(),
-                            font_size: 24.0,
+                            font_size: 18.0,
                             ..default()
                         },
-                        TextColor(Color::WHITE),
+                        TextColor(Color::srgb(0.7, 0.7, 0.7)),
                     ));
 
                     // Restart Button
@@ -198,6 +397,32 @@ pub fn setup_game_over_screen(
                                 TextColor(Color::WHITE),
                             ));
                         });
+
+                    // Watch replay Button
+                    if replay.0.is_some() {
+                        panel.spawn((
+                            Button,
+                            MenuButtonAction::WatchReplay,
+                            Node {
+                                padding: UiRect::all(Val::Px(12.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    Text::new("Watch replay"),
+                                    TextFont {
+                                        font: font.clone(),
+                                        font_size: 24.0,
+                                        ..default()
+                                    },
+                                    TextColor(Color::WHITE),
+                                ));
+                            });
+                    }
                 });
         });
 }
@@ -212,9 +437,24 @@ pub fn cleanup_game_over_screen(
     }
 }
 
+// Settings the menu buttons change
+#[derive(SystemParam)]
+pub struct MenuOptions<'w> {
+    rules: ResMut<'w, GameRules>,
+    players: ResMut<'w, PlayerCount>,
+    rivals: ResMut<'w, Rivals>,
+    autopilot: ResMut<'w, Autopilot>,
+    agents: Res<'w, AgentRegistry>,
+    levels: ResMut<'w, Levels>,
+    seed: ResMut<'w, GameSeed>,
+    settings: ResMut<'w, GameSettings>,
+}
+
 // Button interaction system
 pub fn menu_button_system(
     mut next_state: ResMut<NextState<GameState>>,
+    mut options: MenuOptions,
+    mut countdown: ResMut<ResumeCountdown>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &MenuButtonAction),
         (Changed<Interaction>, With<Button>),
@@ -224,11 +464,7 @@ pub fn menu_button_system(
         match *interaction {
             Interaction::Pressed => {
                 *color = BackgroundColor(Color::srgb(0.5, 0.5, 0.5));
-                match action {
-                    MenuButtonAction::Play | MenuButtonAction::Restart => {
-                        next_state.set(GameState::Playing);
-                    }
-                }
+                apply_menu_action(*action, &mut next_state, &mut options, &mut countdown);
             }
             Interaction::Hovered => {
                 *color = BackgroundColor(Color::WHITE);
@@ -240,6 +476,103 @@ pub fn menu_button_system(
     }
 }
 
+// Carry out a menu button, shared by the mouse and the gamepad
+pub fn apply_menu_action(
+    action: MenuButtonAction,
+    next_state: &mut NextState<GameState>,
+    options: &mut MenuOptions,
+    countdown: &mut ResumeCountdown,
+) {
+    let MenuOptions { rules, players, rivals, autopilot, agents, levels, seed, settings } = options;
+    match action {
+        MenuButtonAction::Play | MenuButtonAction::Restart => {
+            next_state.set(GameState::Playing);
+        }
+        MenuButtonAction::WatchReplay => {
+            next_state.set(GameState::Replay);
+        }
+        MenuButtonAction::Resume => {
+            resume_game(next_state, countdown);
+        }
+        MenuButtonAction::QuitToMenu => {
+            next_state.set(GameState::Menu);
+        }
+        MenuButtonAction::Controls => {
+            next_state.set(GameState::Controls);
+        }
+        MenuButtonAction::OpenEditor => {
+            next_state.set(GameState::Editor);
+        }
+        MenuButtonAction::ToggleBoard => {
+            rules.0.board = match rules.0.board {
+                BoardMode::Walled => BoardMode::Wrap,
+                BoardMode::Wrap => BoardMode::Walled,
+            };
+        }
+        MenuButtonAction::CycleDifficulty => {
+            rules.0 = rules.0.with_difficulty(rules.0.difficulty.next());
+        }
+        MenuButtonAction::CyclePlayers => {
+            players.0 = players.0 % MAX_LOCAL_PLAYERS + 1;
+        }
+        MenuButtonAction::CycleRivals => {
+            // Room is left for at least one human
+            rivals.count = (rivals.count + 1) % MAX_PLAYERS;
+        }
+        MenuButtonAction::CycleAiSkill => {
+            rivals.skill = rivals.skill.next();
+        }
+        MenuButtonAction::CycleAutopilot => {
+            // Off, then every registered agent in turn
+            let names: Vec<_> = agents.names().collect();
+            let next = match autopilot.enabled {
+                true => names.iter().position(|&name| name == autopilot.agent).map_or(0, |index| index + 1),
+                false => 0,
+            };
+            match names.get(next) {
+                Some(name) => {
+                    autopilot.enabled = true;
+                    autopilot.agent = name.to_string();
+                }
+                None => autopilot.enabled = false,
+            }
+        }
+        MenuButtonAction::CycleLevel => {
+            // The open board, then every level in turn
+            let next = levels.selected.map_or(0, |index| index + 1);
+            levels.selected = (next < levels.levels.len()).then_some(next);
+        }
+        MenuButtonAction::ToggleSeed => {
+            // Keep playing the board of the last game, or go back to a fresh one each game
+            seed.fixed = match seed.fixed {
+                Some(_) => None,
+                None => Some(seed.current),
+            };
+            settings.settings.seed = seed.fixed;
+            save_settings(settings);
+        }
+    }
+}
+
+// Keep the menu button texts in sync with the settings
+pub fn update_menu_texts(
+    settings: MenuSettings,
+    mut texts: Query<(&MenuText, &mut Text)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (&menu_text, mut text) in &mut texts {
+        text.0 = menu_label(menu_text, &settings);
+    }
+}
+
+// Run condition for the systems that drive the snake
+pub fn game_running(state: Res<State<GameState>>, countdown: Res<ResumeCountdown>) -> bool {
+    matches!(state.get(), GameState::Playing | GameState::Replay) && countdown.0.is_none()
+}
+
 // Set Game Over if we receive a GameOverEvent
 pub fn handle_game_over(
     mut reader: MessageReader<GameOverEvent>,
@@ -251,11 +584,39 @@ pub fn handle_game_over(
     }    
 }
 
-// Build the score UI
+// Start over with the restart key
+pub fn restart_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut countdown: ResMut<ResumeCountdown>,
+    mut restart_writer: MessageWriter<RestartGame>,
+) {
+    if !settings.settings.key_map.just_pressed(Action::Restart, &keyboard_input) {
+        return;
+    }
+
+    countdown.0 = None;
+
+    // Entering the running state again would not reset the game
+    if *state.get() == GameState::Playing {
+        restart_writer.write(RestartGame);
+    } else {
+        next_state.set(GameState::Playing);
+    }
+}
+
+// Build the score UI, restarting from the pause menu enters the game again with the old one still up
 pub fn setup_score_ui(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    ui_query: Query<Entity, With<ScoreUI>>,
 ) {
+    for entity in &ui_query {
+        commands.entity(entity).despawn();
+    }
+
     let font = asset_server.load("fonts/FiraSans.ttf");
 
     commands.spawn((
@@ -279,21 +640,48 @@ pub fn setup_score_ui(
 // Update the scores when eating food
 pub fn update_score_ui(
     score: Res<Score>,
+    sim: Option<Res<Sim>>,
     mut query: Query<&mut Text, With<ScoreUI>>,
 ) {
     if score.is_changed() {
         if let Ok(mut text) = query.single_mut() {
-            text.0 = format!("Score: {}", score.0);
+            // Speed in tiles per second
+            let speed = sim.as_ref().map_or(0.0, |sim| 1000.0 / sim.0.tick_millis() as f32);
+            let humans = sim.map_or(1, |sim| sim.0.humans());
+            text.0 = format!("{}   Speed: {:.1}", scores_label(&score.0, humans), speed);
         }
     }
 }
 
+// Score of a single player or of every player in a versus game
+fn scores_label(scores: &[u32], humans: usize) -> String {
+    match scores {
+        [] => "Score: 0".to_string(),
+        [score] => format!("Score: {score}"),
+        _ => scores
+            .iter()
+            .enumerate()
+            .map(|(player, score)| format!("{}: {score}", player_name(player, humans)))
+            .collect::<Vec<_>>()
+            .join("   "),
+    }
+}
+
+// Humans come first, the computer rivals after them
+pub fn player_name(player: usize, humans: usize) -> String {
+    if player < humans {
+        format!("Player {}", player + 1)
+    } else {
+        format!("CPU {}", player - humans + 1)
+    }
+}
+
 // Remove the Score UI
 pub fn cleanup_score_ui(
     mut commands: Commands,
     ui_query: Query<Entity, With<ScoreUI>>,
 ) {
-    if let Ok(entity) = ui_query.single() {
+    for entity in &ui_query {
         commands.entity(entity).despawn();
     }
 }
@@ -303,53 +691,260 @@ pub fn reset_game(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
     mut segments: ResMut<SnakeSegments>,
-    mut last_tail_position: ResMut<LastTailPosition>,
     mut score: ResMut<Score>,
-    snake_query: Query<Entity, With<SnakeHead>>,
-    segment_query: Query<Entity, With<SnakeSegment>>,
-    food_query: Query<Entity, With<Food>>,
+    mut timer: ResMut<MovementTimer>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut seed: ResMut<GameSeed>,
+    grid_config: Res<GridConfig>,
+    levels: Res<Levels>,
+    rules: Res<GameRules>,
+    players: Res<PlayerCount>,
+    rivals: Res<Rivals>,
+    game_entities: Query<Entity, GameEntityFilter>,
 ) {
-
-    // Despawn the old snake
-    for entity in snake_query.iter() {
+    // Despawn the old snake, food and borders
+    for entity in game_entities.iter() {
         commands.entity(entity).despawn();
     }
-    for entity in segment_query.iter() {
-        commands.entity(entity).despawn();
+
+    recorder.0.clear();
+
+    // A level brings its own board
+    let level = levels.current();
+    let grid = level.map_or(*grid_config, |level| level.grid);
+    let layout = level.map(|level| level.layout.clone()).unwrap_or_default();
+    let fits = |snakes: usize| match level {
+        Some(level) => level.validate(snakes).is_ok(),
+        None => grid.validate(snakes).is_ok(),
+    };
+
+    // Small boards may not have room for every snake, drop the rivals first
+    let rival_count = rivals.count.min(MAX_PLAYERS.saturating_sub(players.0));
+    let lineups = [(players.0, rival_count), (players.0, 0), (1, 0)];
+    let (humans, rival_count) = lineups
+        .into_iter()
+        .find(|&(humans, rival_count)| fits(humans + rival_count))
+        .unwrap_or((1, 0));
+    if (humans, rival_count) != lineups[0] {
+        warn!("The board is too small for {} snakes, starting with {}", lineups[0].0 + lineups[0].1, humans + rival_count);
     }
+    score.0 = vec![0; humans + rival_count];
 
-    // Despawn the food
-    for entity in food_query.iter() {
-        commands.entity(entity).despawn();
+    // Start a fresh simulation
+    let sim = SnakeSim::with_players(grid, rules.0, seed.next_game(), humans + rival_count)
+        .with_rivals(rival_count)
+        .with_layout(layout);
+    *timer = MovementTimer::from_millis(sim.tick_millis());
+    spawn_game(&mut commands, &mut meshes, &mut materials, &asset_server, &mut segments, &sim);
+
+    // The rivals steer themselves
+    for player in humans..sim.players() {
+        commands.entity(segments.0[player][0]).insert(AiSnake::new(rivals.skill.agent()));
     }
+    commands.insert_resource(Sim(sim));
+}
 
+// Spawn the snakes, food and border entities of a simulation
+pub fn spawn_game(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    asset_server: &AssetServer,
+    segments: &mut SnakeSegments,
+    sim: &SnakeSim,
+) {
     segments.0.clear();
-    last_tail_position.0 = None;
-    score.0 = 0;
 
-    // Spawn the snake head
-    let mesh = meshes.add(Rectangle::new(1.0, 1.0));
+    // A wrap-around board has no borders
+    if sim.rules().board == BoardMode::Walled {
+        spawn_borders(commands, meshes, materials, sim.grid());
+    }
+    spawn_walls(commands, meshes, materials, &sim.layout().walls);
+
+    for (player, snake) in sim.snakes().iter().enumerate() {
+        let head_id = spawn_head(commands, meshes, materials, snake.head(), snake.direction(), player);
+        let mut snake_segments = vec![head_id];
+
+        // Spawn the tail segments
+        for pos in snake.body().skip(1) {
+            let tail_id = spawn_segment(
+                commands,
+                meshes,
+                materials,
+                pos,
+                player,
+            );
+            snake_segments.push(tail_id);
+        }
+        segments.0.push(snake_segments);
+    }
+
+    // Spawn the food
+    for &food in sim.food() {
+        spawn_food(commands, asset_server, food);
+    }
+}
 
-    let head_id = commands.spawn((
-        Mesh2d(mesh),
-        MeshMaterial2d(materials.add(SNAKE_HEAD_COLOR)),
-        Position { x: 3, y: 3 },
+// Spawn the head of a snake in the color of its player
+pub fn spawn_head(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Position,
+    direction: Direction,
+    player: usize,
+) -> Entity {
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::new(1.0, 1.0))),
+        MeshMaterial2d(materials.add(SNAKE_HEAD_COLORS[player % MAX_PLAYERS])),
+        position,
         Size::square(1.0),
-        SnakeHead { direction: Direction::Up },
+        SnakeHead { direction, player },
+        DirectionQueue::default(),
         Transform::default(),
         GlobalTransform::default(),
     ))
-    .id();
+    .id()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        asset::AssetPlugin,
+        ecs::system::RunSystemOnce,
+        input::{
+            keyboard::{Key, KeyboardInput},
+            ButtonState, InputPlugin,
+        },
+        state::app::StatesPlugin,
+        window::WindowFocused,
+    };
 
-    // Spawn the first tail segment
+    use super::*;
+    use crate::{
+        settings::Settings,
+        systems::pause::{counting_down, PausePlugin},
+    };
 
-    let tail_id = spawn_segment(
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-        Position { x:3, y: 2},
-    );
+    // The game states wired up like in main, without a window
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), InputPlugin, StatesPlugin, PausePlugin))
+            .init_asset::<Font>()
+            .init_asset::<Image>()
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_state::<GameState>()
+            .add_message::<WindowFocused>()
+            .init_resource::<ResumeCountdown>()
+            .init_resource::<Score>()
+            .init_resource::<SnakeSegments>()
+            .init_resource::<MovementTimer>()
+            .init_resource::<ReplayRecorder>()
+            .init_resource::<GridConfig>()
+            .init_resource::<Levels>()
+            .init_resource::<GameRules>()
+            .init_resource::<PlayerCount>()
+            .init_resource::<Rivals>()
+            .init_resource::<Autopilot>()
+            .init_resource::<AgentRegistry>()
+            .insert_resource(GameSeed::new(None))
+            .insert_resource(GameSettings {
+                settings: Settings::default(),
+                path: std::env::temp_dir().join("snake_state_test.ron"),
+            })
+            .add_systems(OnEnter(GameState::Playing), (reset_game, setup_score_ui).run_if(not(counting_down)))
+            .add_systems(OnEnter(GameState::Menu), cleanup_score_ui)
+            .add_systems(Update, update_score_ui);
+        app.update();
+        app
+    }
+
+    fn set_state(app: &mut App, state: GameState) {
+        app.world_mut().resource_mut::<NextState<GameState>>().set(state);
+        app.update();
+    }
 
-    *segments = SnakeSegments(vec![head_id, tail_id]);
-}
\ No newline at end of file
+    fn state(app: &App) -> GameState {
+        *app.world().resource::<State<GameState>>().get()
+    }
+
+    // Press a key for one frame and let go of it in the next
+    fn tap(app: &mut App, key_code: KeyCode, logical_key: Key) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world_mut().write_message(KeyboardInput {
+                key_code,
+                logical_key: logical_key.clone(),
+                state,
+                text: None,
+                repeat: false,
+                window: Entity::PLACEHOLDER,
+            });
+            app.update();
+        }
+    }
+
+    // Click a button of the pause menu
+    fn press(app: &mut App, action: MenuButtonAction) {
+        app.world_mut()
+            .run_system_once(
+                move |mut next_state: ResMut<NextState<GameState>>, mut options: MenuOptions, mut countdown: ResMut<ResumeCountdown>| {
+                    apply_menu_action(action, &mut next_state, &mut options, &mut countdown);
+                },
+            )
+            .unwrap();
+        app.update();
+    }
+
+    fn head(app: &App) -> Entity {
+        app.world().resource::<SnakeSegments>().0[0][0]
+    }
+
+    fn score_uis(app: &mut App) -> Vec<(Entity, String)> {
+        let mut query = app.world_mut().query_filtered::<(Entity, &Text), With<ScoreUI>>();
+        query.iter(app.world()).map(|(entity, text)| (entity, text.0.clone())).collect()
+    }
+
+    #[test]
+    fn resuming_keeps_the_game_and_restarting_starts_over() {
+        let mut app = app();
+        set_state(&mut app, GameState::Playing);
+        app.world_mut().resource_mut::<Score>().0 = vec![5];
+        app.update();
+        let first_head = head(&app);
+        let [(first_ui, _)] = score_uis(&mut app)[..] else {
+            panic!("expected a single score UI");
+        };
+
+        // Resume counts down over the same board and score
+        tap(&mut app, KeyCode::Escape, Key::Escape);
+        assert_eq!(state(&app), GameState::Paused);
+        press(&mut app, MenuButtonAction::Resume);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(app.world().resource::<ResumeCountdown>().0.is_some());
+        assert_eq!(head(&app), first_head);
+        let uis = score_uis(&mut app);
+        assert_eq!(uis.len(), 1);
+        assert_eq!(uis[0].0, first_ui);
+        assert!(uis[0].1.starts_with("Score: 5"), "{}", uis[0].1);
+
+        // Restart sets up a new board with a single, fresh score UI
+        tap(&mut app, KeyCode::Escape, Key::Escape);
+        assert_eq!(state(&app), GameState::Paused);
+        press(&mut app, MenuButtonAction::Restart);
+        assert_eq!(state(&app), GameState::Playing);
+        assert!(app.world().resource::<ResumeCountdown>().0.is_none());
+        assert_ne!(head(&app), first_head);
+        assert!(app.world().get_entity(first_head).is_err());
+        assert_eq!(app.world().resource::<Score>().0, [0]);
+        let uis = score_uis(&mut app);
+        assert_eq!(uis.len(), 1);
+        assert_ne!(uis[0].0, first_ui);
+        assert!(uis[0].1.starts_with("Score: 0"), "{}", uis[0].1);
+
+        set_state(&mut app, GameState::Menu);
+        assert!(score_uis(&mut app).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use serde::Serialize;
+
+use crate::{
+    agent::{AgentRegistry, BoardView},
+    sim::{GridConfig, Rules, SnakeSim},
+};
+
+// Every agent plays every seed on the same board and rules
+#[derive(Debug, Clone)]
+pub struct TournamentConfig {
+    pub agents: Vec<String>,
+    pub seeds: Vec<u64>,
+    pub grid: GridConfig,
+    pub rules: Rules,
+    pub max_steps: u32, // games still running after this many steps end as a timeout
+    pub threads: Option<usize>, // every CPU core when not given
+}
+
+// Outcome of one agent playing one seed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameResult {
+    pub agent: String,
+    pub seed: u64,
+    pub score: u32,
+    pub length: usize,
+    pub steps: u32,
+    pub death: &'static str, // cause of death, `timeout` or `panic` when the agent crashed
+}
+
+// Results of one agent over all seeds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgentSummary {
+    pub rank: usize,
+    pub agent: String,
+    pub games: usize,
+    pub mean_score: f64,
+    pub best_score: u32,
+    pub mean_length: f64,
+    pub mean_steps: f64,
+    pub deaths: BTreeMap<&'static str, usize>,
+}
+
+// Play every game, spread over the CPU cores. The results come in the order of the
+// agents and then the seeds, whatever thread played them.
+pub fn run_tournament(config: &TournamentConfig, registry: &AgentRegistry) -> Result<Vec<GameResult>, String> {
+    if let Some(unknown) = config.agents.iter().find(|name| !registry.contains(name)) {
+        let names: Vec<_> = registry.names().collect();
+        return Err(format!("unknown agent `{unknown}`, available agents: {}", names.join(", ")));
+    }
+    config.grid.validate(1)?;
+
+    let games: Vec<(&str, u64)> = config
+        .agents
+        .iter()
+        .flat_map(|agent| config.seeds.iter().map(move |&seed| (agent.as_str(), seed)))
+        .collect();
+    let results = Mutex::new(vec![None; games.len()]);
+    let next_game = AtomicUsize::new(0);
+
+    let threads = config
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()))
+        .clamp(1, games.len().max(1));
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let index = next_game.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(agent, seed)) = games.get(index) else {
+                        break;
+                    };
+                    let result = play_game(config, registry, agent, seed);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+// A single game of an agent alone on the board. An agent that panics loses the
+// game with the death `panic` instead of taking the whole tournament down.
+pub fn play_game(config: &TournamentConfig, registry: &AgentRegistry, agent: &str, seed: u64) -> Result<GameResult, String> {
+    let mut sim = SnakeSim::new(config.grid, config.rules, seed);
+    let mut bot = registry
+        .create(agent)
+        .ok_or_else(|| format!("agent `{agent}` is not registered"))?;
+
+    let mut panicked = false;
+    while !sim.is_over() && sim.ticks() < config.max_steps {
+        let Ok(direction) = panic::catch_unwind(AssertUnwindSafe(|| bot.next_direction(&BoardView::new(&sim, 0)))) else {
+            panicked = true;
+            break;
+        };
+        sim.tick(&[direction]);
+    }
+
+    let snake = sim.snake(0);
+    let death = match snake.death() {
+        _ if panicked => "panic",
+        Some(cause) => cause.name(),
+        None => "timeout",
+    };
+    Ok(GameResult {
+        agent: agent.to_string(),
+        seed,
+        score: snake.score(),
+        length: snake.length(),
+        steps: sim.ticks(),
+        death,
+    })
+}
+
+// One line per agent, best mean score first
+pub fn summarize(results: &[GameResult]) -> Vec<AgentSummary> {
+    let mut by_agent: Vec<(&str, Vec<&GameResult>)> = Vec::new();
+    for result in results {
+        match by_agent.iter_mut().find(|(agent, _)| *agent == result.agent) {
+            Some((_, games)) => games.push(result),
+            None => by_agent.push((&result.agent, vec![result])),
+        }
+    }
+
+    let mean = |games: &[&GameResult], value: fn(&GameResult) -> f64| {
+        games.iter().map(|game| value(game)).sum::<f64>() / games.len() as f64
+    };
+
+    let mut summaries: Vec<_> = by_agent
+        .into_iter()
+        .map(|(agent, games)| {
+            let mut deaths = BTreeMap::new();
+            for game in &games {
+                *deaths.entry(game.death).or_default() += 1;
+            }
+
+            AgentSummary {
+                rank: 0,
+                agent: agent.to_string(),
+                games: games.len(),
+                mean_score: mean(&games, |game| f64::from(game.score)),
+                best_score: games.iter().map(|game| game.score).max().unwrap_or(0),
+                mean_length: mean(&games, |game| game.length as f64),
+                mean_steps: mean(&games, |game| f64::from(game.steps)),
+                deaths,
+            }
+        })
+        .collect();
+
+    // Surviving longer breaks ties
+    summaries.sort_by(|a, b| {
+        b.mean_score
+            .total_cmp(&a.mean_score)
+            .then(b.mean_steps.total_cmp(&a.mean_steps))
+            .then(a.agent.cmp(&b.agent))
+    });
+    for (index, summary) in summaries.iter_mut().enumerate() {
+        summary.rank = index + 1;
+    }
+
+    summaries
+}
+
+// Ranking and every game as pretty printed JSON
+pub fn to_json(summaries: &[AgentSummary], results: &[GameResult]) -> String {
+    #[derive(Serialize)]
+    struct Report<'a> {
+        ranking: &'a [AgentSummary],
+        games: &'a [GameResult],
+    }
+
+    serde_json::to_string_pretty(&Report { ranking: summaries, games: results }).expect("results serialize to JSON")
+}
+
+// Every game as CSV with a header line
+pub fn to_csv(results: &[GameResult]) -> String {
+    let mut csv = String::from("agent,seed,score,length,steps,death\n");
+    for game in results {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            game.agent, game.seed, game.score, game.length, game.steps, game.death
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{agent::Agent, components::Direction};
+
+    // Goes straight for a few ticks, then crashes
+    struct Crashing;
+
+    impl Agent for Crashing {
+        fn next_direction(&mut self, board: &BoardView) -> Direction {
+            assert!(board.ticks() < 3, "bot crashed");
+            board.direction()
+        }
+    }
+
+    fn config(agents: &[&str], threads: Option<usize>) -> TournamentConfig {
+        TournamentConfig {
+            agents: agents.iter().map(|agent| agent.to_string()).collect(),
+            seeds: (10..16).collect(),
+            grid: GridConfig { width: 8, height: 8, ..GridConfig::default() },
+            rules: Rules::default(),
+            max_steps: 400,
+            threads,
+        }
+    }
+
+    #[test]
+    fn same_seeds_give_the_same_results_on_any_number_of_threads() {
+        let registry = AgentRegistry::default();
+        let agents = ["astar", "greedy", "cautious"];
+        let results = run_tournament(&config(&agents, Some(1)), &registry).unwrap();
+        assert_eq!(results.len(), agents.len() * 6);
+        assert_eq!((results[0].agent.as_str(), results[0].seed), ("astar", 10));
+        assert_eq!((results[6].agent.as_str(), results[6].seed), ("greedy", 10));
+
+        for threads in [Some(1), Some(2), Some(5), None] {
+            assert_eq!(run_tournament(&config(&agents, threads), &registry).unwrap(), results, "{threads:?} threads");
+        }
+    }
+
+    #[test]
+    fn a_crashing_agent_loses_its_games() {
+        let mut registry = AgentRegistry::default();
+        registry.register("crashing", || Box::new(Crashing));
+
+        let results = run_tournament(&config(&["crashing", "greedy"], Some(2)), &registry).unwrap();
+        let (crashed, others) = results.split_at(6);
+        assert!(crashed.iter().all(|game| game.death == "panic" && game.steps == 3));
+        assert!(others.iter().all(|game| game.agent == "greedy" && game.death != "panic"));
+        assert_eq!(summarize(&results).last().unwrap().deaths, BTreeMap::from([("panic", 6)]));
+    }
+
+    #[test]
+    fn unknown_agents_are_an_error() {
+        let registry = AgentRegistry::default();
+        assert!(run_tournament(&config(&["nobody"], None), &registry).is_err());
+        assert!(play_game(&config(&[], None), &registry, "nobody", 1).is_err());
+    }
+}